@@ -0,0 +1,98 @@
+use std::{fs, process::ExitCode};
+
+use sjaak::{book::BookBuilder, pgn::parse_pgn};
+
+const USAGE: &str = "\
+Usage: build_book [OPTIONS] <PGN>...
+
+Builds a Polyglot opening book from the games in the given PGN files.
+
+Options:
+  -o, --output <FILE>    Book to write [default: book.bin]
+      --max-ply <N>      Only use the first N plies of every game [default: 20]
+      --min-games <N>    Only include moves played in at least N games [default: 3]
+  -h, --help             Print this message";
+
+struct Args {
+    output: String,
+    max_ply: usize,
+    min_games: u32,
+    inputs: Vec<String>,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args {
+        output: "book.bin".to_string(),
+        max_ply: 20,
+        min_games: 3,
+        inputs: Vec::new(),
+    };
+    let mut iter = std::env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        let mut value = |name: &str| iter.next().ok_or(format!("missing value for {}", name));
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                std::process::exit(0);
+            }
+            "-o" | "--output" => args.output = value(&arg)?,
+            "--max-ply" => {
+                args.max_ply = value(&arg)?
+                    .parse()
+                    .map_err(|err| format!("invalid --max-ply: {}", err))?
+            }
+            "--min-games" => {
+                args.min_games = value(&arg)?
+                    .parse()
+                    .map_err(|err| format!("invalid --min-games: {}", err))?
+            }
+            _ if arg.starts_with('-') => return Err(format!("unknown option {}", arg)),
+            _ => args.inputs.push(arg),
+        }
+    }
+    if args.inputs.is_empty() {
+        return Err("no PGN files given".to_string());
+    }
+    Ok(args)
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("error: {}\n\n{}", err, USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut builder = BookBuilder::new(args.max_ply, args.min_games);
+    let mut total_games = 0;
+    for path in &args.inputs {
+        let text = match fs::read(path) {
+            Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+            Err(err) => {
+                eprintln!("error: could not read {}: {}", path, err);
+                return ExitCode::FAILURE;
+            }
+        };
+        for (index, game) in parse_pgn(&text).iter().enumerate() {
+            if let Err(err) = builder.add_game(game) {
+                eprintln!("warning: {} game {}: {}", path, index + 1, err);
+            }
+            total_games += 1;
+        }
+    }
+
+    let book = builder.build();
+    if let Err(err) = fs::write(&args.output, book.to_bytes()) {
+        eprintln!("error: could not write {}: {}", args.output, err);
+        return ExitCode::FAILURE;
+    }
+    println!(
+        "Wrote {} entries from {} games to {}",
+        book.len(),
+        total_games,
+        args.output
+    );
+    ExitCode::SUCCESS
+}
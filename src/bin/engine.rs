@@ -1,40 +1,266 @@
 use std::fs::File;
 use std::io::{self, BufRead, Write};
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+    mpsc::{self, Receiver},
+};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use sjaak::{
+    book::Book,
+    eval::Breakdown,
+    moves::Move,
+    perft::node::Node,
+    search::{self, Limits},
+    tt::TranspositionTable,
+    uci::{Game, GoParams, Info, Score, UciCommand, format_bestmove},
+};
+
+/// Flags the input thread uses to steer a running search.
+/// Every search gets its own, so a late `stop` can never leak into the next one.
+#[derive(Default)]
+struct SearchFlags {
+    stop: AtomicBool,
+    /// Set while pondering, cleared by `ponderhit`
+    pondering: AtomicBool,
+}
+
+struct SearchJob {
+    node: Node,
+    /// Hashes of the game up to and including `node`
+    history: Vec<u64>,
+    params: GoParams,
+    book_move: Option<Move>,
+    tt: Arc<TranspositionTable>,
+    flags: Arc<SearchFlags>,
+}
+
+struct Engine {
+    game: Game,
+    own_book: bool,
+    book: Option<Book>,
+    rng: tinyrand_std::ThreadLocalRand,
+    /// Shared with the running search. Resizing swaps in a new table, which the next search
+    /// picks up.
+    tt: Arc<TranspositionTable>,
+}
+
+impl Engine {
+    fn new() -> Self {
+        Engine {
+            game: Game::default(),
+            own_book: false,
+            book: None,
+            rng: tinyrand_std::thread_rand(),
+            tt: Arc::new(TranspositionTable::default()),
+        }
+    }
+
+    fn set_option(&mut self, name: &str, value: Option<&str>) {
+        match (name.to_ascii_lowercase().as_str(), value) {
+            ("hash", Some(value)) => match value.parse::<usize>() {
+                Ok(size_mb) => {
+                    let size_mb = size_mb.clamp(1, TranspositionTable::MAX_SIZE_MB);
+                    self.tt = Arc::new(TranspositionTable::new(size_mb));
+                }
+                Err(err) => info_string(&format!("invalid Hash {:?}: {}", value, err)),
+            },
+            ("ownbook", Some(value)) => self.own_book = value == "true",
+            ("bookfile", Some("")) | ("bookfile", Some("<empty>")) => self.book = None,
+            ("bookfile", Some(path)) => match Book::open(path) {
+                Ok(book) => self.book = Some(book),
+                Err(err) => {
+                    self.book = None;
+                    info_string(&format!("{}: {}", path, err));
+                }
+            },
+            _ => info_string(&format!("unknown option {:?}", name)),
+        }
+    }
+
+    fn book_move(&mut self) -> Option<Move> {
+        if !self.own_book {
+            return None;
+        }
+        self.book
+            .as_ref()?
+            .weighted_move(&self.game.node, &mut self.rng)
+    }
+}
+
+fn info_string(message: &str) {
+    println!(
+        "{}",
+        Info {
+            string: Some(message.to_string()),
+            ..Info::default()
+        }
+    );
+}
+
+fn search(job: SearchJob) {
+    let SearchJob {
+        node,
+        history,
+        params,
+        book_move,
+        tt,
+        flags,
+    } = job;
+
+    let (best, ponder) = match book_move {
+        Some(mv) => {
+            info_string("book move");
+            (Some(mv), None)
+        }
+        None => {
+            let start = Instant::now();
+            let limits = Limits {
+                depth: params.depth,
+                nodes: params.nodes,
+                time: params.time_budget(node.side),
+            };
+            let result = search::search(&node, &history, &tt, limits, &flags.stop, |result| {
+                println!(
+                    "{}",
+                    Info {
+                        depth: Some(result.depth as u32),
+                        score: Some(Score::from_search(result.score)),
+                        nodes: Some(result.nodes),
+                        time: Some(start.elapsed()),
+                        hashfull: Some(tt.hashfull()),
+                        pv: result.pv.clone(),
+                        ..Info::default()
+                    }
+                );
+            });
+            (result.best_move, result.pv.get(1).copied())
+        }
+    };
+
+    // UCI forbids sending bestmove during an infinite search or while pondering, even if we're
+    // done early
+    while !flags.stop.load(Ordering::Relaxed)
+        && (params.infinite || flags.pondering.load(Ordering::Relaxed))
+    {
+        thread::sleep(Duration::from_millis(1));
+    }
+    println!("{}", format_bestmove(best, ponder));
+}
+
+fn search_worker(jobs: Receiver<SearchJob>) {
+    for job in jobs {
+        search(job);
+    }
+}
 
 fn main() -> io::Result<()> {
     let mut log_file = File::create("/tmp/sjaak_engine.log")?;
     writeln!(log_file, "Startup complete")?;
 
+    let mut engine = Engine::new();
+    let (jobs, worker_jobs) = mpsc::channel();
+    let worker = thread::spawn(move || search_worker(worker_jobs));
+    // The flags of the last search, and how long it may run after a ponderhit
+    let mut current: Option<(Arc<SearchFlags>, Option<Duration>)> = None;
+
     for line in io::stdin().lock().lines() {
         let line = line?;
         writeln!(log_file, "<< {}", line)?;
         eprintln!("<< {}", line);
-        let mut parts = line.trim().split_whitespace();
-        let command = parts.next();
+
+        let command = match UciCommand::parse(&line) {
+            Ok(command) => command,
+            Err(err) => {
+                writeln!(log_file, "!! {}", err)?;
+                eprintln!("!! {}", err);
+                continue;
+            }
+        };
 
         match command {
-            Some("uci") => {
+            UciCommand::Uci => {
                 println!("id name sjaak");
                 println!("id author jmc");
+                println!(
+                    "option name Hash type spin default {} min 1 max {}",
+                    TranspositionTable::DEFAULT_SIZE_MB,
+                    TranspositionTable::MAX_SIZE_MB
+                );
+                println!("option name OwnBook type check default false");
+                println!("option name BookFile type string default <empty>");
                 println!("uciok");
             }
-            Some("isready") => {
+            UciCommand::Debug(_) => {}
+            UciCommand::IsReady => {
                 println!("readyok");
             }
-            Some("ucinewgame") => {
-                // We don't need to do anything here yet.
+            UciCommand::SetOption { name, value } => engine.set_option(&name, value.as_deref()),
+            UciCommand::UciNewGame => {
+                engine.game = Game::default();
+                engine.tt.clear();
+            }
+            UciCommand::Position { start, moves } => match Game::new(&start, &moves) {
+                Ok(game) => engine.game = game,
+                Err(err) => info_string(&err.to_string()),
+            },
+            UciCommand::Go(params) => {
+                // A new go while still searching cancels the old search
+                if let Some((flags, _)) = current.take() {
+                    flags.stop.store(true, Ordering::Relaxed);
+                }
+                let flags = Arc::new(SearchFlags::default());
+                flags.pondering.store(params.ponder, Ordering::Relaxed);
+                let ponderhit_budget = GoParams {
+                    ponder: false,
+                    ..params.clone()
+                }
+                .time_budget(engine.game.node.side);
+                current = Some((flags.clone(), ponderhit_budget));
+                let job = SearchJob {
+                    node: engine.game.node.clone(),
+                    history: engine.game.hashes.clone(),
+                    book_move: engine.book_move(),
+                    tt: engine.tt.clone(),
+                    params,
+                    flags,
+                };
+                jobs.send(job).expect("search thread died");
             }
-            Some("position") => {
-                // We don't need to do anything here yet.
+            UciCommand::Stop => {
+                if let Some((flags, _)) = &current {
+                    flags.stop.store(true, Ordering::Relaxed);
+                }
             }
-            Some("go") => {
-                // We don't need to do anything here yet.
+            UciCommand::PonderHit => {
+                // The ponder search runs without a time limit, so arm one now that it's our move
+                if let Some((flags, budget)) = &current {
+                    flags.pondering.store(false, Ordering::Relaxed);
+                    if let Some(budget) = *budget {
+                        let flags = flags.clone();
+                        thread::spawn(move || {
+                            thread::sleep(budget);
+                            flags.stop.store(true, Ordering::Relaxed);
+                        });
+                    }
+                }
             }
-            Some("stop") => {
-                break;
+            UciCommand::Quit => break,
+            UciCommand::Eval => {
+                for line in Breakdown::new(&engine.game.node).to_string().lines() {
+                    info_string(line);
+                }
             }
-            _ => {}
         }
     }
+
+    // Also reached when stdin closes, which we treat as quit
+    if let Some((flags, _)) = &current {
+        flags.stop.store(true, Ordering::Relaxed);
+    }
+    drop(jobs);
+    worker.join().expect("search thread panicked");
     Ok(())
 }
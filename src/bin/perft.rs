@@ -0,0 +1,219 @@
+use std::{fs, process::ExitCode, time::Instant};
+
+use sjaak::{
+    perft::{cache::PerftCache, node::Node, suite::parse_suite},
+    position::Position,
+    uci::parse_uci_move,
+};
+
+const USAGE: &str = "\
+Usage: perft [OPTIONS] <DEPTH> [FEN]
+       perft [OPTIONS] --suite <FILE> <DEPTH>
+
+Counts the leaf nodes of the legal move tree, split up by root move in the same format as
+Stockfish's `go perft`. The position defaults to the start position.
+
+With --suite, checks the counts of every position in an EPD file in the perftsuite.epd format
+(`<FEN> ;D1 <count> ;D2 <count> ...`) up to DEPTH instead, and reports the first wrong depth of
+each position.
+
+Options:
+  -m, --moves <MOVES>    Play these space separated UCI moves first
+  -t, --threads <N>      Number of threads to count with [default: number of CPUs]
+      --hash <MB>        Cache subtree counts in a table of this size, 0 to disable [default: 0]
+  -s, --suite <FILE>     Check the positions in an EPD file
+      --stats            Count captures, checks, mates etc. instead of splitting up by root move,
+                         on one thread
+  -h, --help             Print this message";
+
+struct Args {
+    depth: u8,
+    fen: String,
+    moves: Vec<String>,
+    threads: usize,
+    hash_mb: usize,
+    suite: Option<String>,
+    stats: bool,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut depth = None;
+    let mut fen = Vec::new();
+    let mut moves = Vec::new();
+    let mut threads = None;
+    let mut hash_mb = None;
+    let mut suite = None;
+    let mut stats = false;
+    let mut iter = std::env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                std::process::exit(0);
+            }
+            "-m" | "--moves" => {
+                let value = iter.next().ok_or(format!("missing value for {}", arg))?;
+                moves.extend(value.split_whitespace().map(str::to_string));
+            }
+            "-t" | "--threads" => {
+                threads = Some(
+                    iter.next()
+                        .ok_or(format!("missing value for {}", arg))?
+                        .parse()
+                        .map_err(|err| format!("invalid --threads: {}", err))?,
+                )
+            }
+            "--hash" => {
+                hash_mb = Some(
+                    iter.next()
+                        .ok_or(format!("missing value for {}", arg))?
+                        .parse()
+                        .map_err(|err| format!("invalid --hash: {}", err))?,
+                )
+            }
+            "--stats" => stats = true,
+            "-s" | "--suite" => {
+                suite = Some(iter.next().ok_or(format!("missing value for {}", arg))?)
+            }
+            // A lone dash is part of a FEN
+            _ if arg.starts_with('-') && arg != "-" => {
+                return Err(format!("unknown option {}", arg));
+            }
+            // The FEN may be passed as one argument or as several
+            _ if depth.is_some() => fen.push(arg),
+            _ => {
+                depth = Some(
+                    arg.parse()
+                        .map_err(|err| format!("invalid depth {}: {}", arg, err))?,
+                )
+            }
+        }
+    }
+    if suite.is_some() && (!fen.is_empty() || !moves.is_empty()) {
+        return Err("--suite can't be combined with a FEN or moves".to_string());
+    }
+    // The statistics are counted on one thread, without a cache
+    if stats && (threads.is_some() || hash_mb.is_some() || suite.is_some()) {
+        return Err("--stats can't be combined with --threads, --hash or --suite".to_string());
+    }
+    Ok(Args {
+        depth: depth.ok_or("no depth given")?,
+        fen: if fen.is_empty() {
+            Position::START_POS.to_fen()
+        } else {
+            fen.join(" ")
+        },
+        moves,
+        threads: threads.unwrap_or_else(num_cpus::get),
+        hash_mb: hash_mb.unwrap_or(0),
+        suite,
+        stats,
+    })
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("error: {}\n\n{}", err, USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let cache = (args.hash_mb > 0).then(|| PerftCache::new(args.hash_mb));
+    let count = |node: &Node, depth| match &cache {
+        Some(cache) => node.perft_parallel_hashed(depth, args.threads, cache),
+        None => node.perft_parallel(depth, args.threads),
+    };
+    if let Some(path) = &args.suite {
+        return run_suite(path, args.depth, count);
+    }
+
+    let mut node: Node = match Position::parse_fen(&args.fen) {
+        Ok(position) => position.to_node(),
+        Err(err) => {
+            eprintln!("error: invalid FEN: {}", err);
+            return ExitCode::FAILURE;
+        }
+    };
+    for uci in &args.moves {
+        match parse_uci_move(&node, uci) {
+            Some(mv) => node = node.make_move(mv),
+            None => {
+                eprintln!("error: illegal move {}", uci);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    if args.stats {
+        let start = Instant::now();
+        println!("{}", node.perft_stats(args.depth));
+        println!("Time: {} ms", start.elapsed().as_millis());
+        return ExitCode::SUCCESS;
+    }
+
+    let start = Instant::now();
+    let divide: Vec<_> = if args.depth == 0 {
+        Vec::new()
+    } else {
+        node.legal_moves()
+            .iter()
+            .map(|&mv| (mv, count(&node.make_move(mv), args.depth - 1)))
+            .collect()
+    };
+    let nodes = if args.depth == 0 {
+        1
+    } else {
+        divide.iter().map(|&(_, leaves)| leaves).sum()
+    };
+    let elapsed = start.elapsed();
+    for (mv, leaves) in &divide {
+        println!("{}: {}", mv, leaves);
+    }
+    println!();
+    println!("Nodes searched: {}", nodes);
+    println!(
+        "Time: {} ms ({} nodes/s)",
+        elapsed.as_millis(),
+        (nodes as f64 / elapsed.as_secs_f64().max(1e-9)) as u64
+    );
+    ExitCode::SUCCESS
+}
+
+fn run_suite<F: Fn(&Node, u8) -> u64>(path: &str, max_depth: u8, count: F) -> ExitCode {
+    let entries = match fs::read_to_string(path) {
+        Ok(text) => match parse_suite(&text) {
+            Ok(entries) => entries,
+            Err(err) => {
+                eprintln!("error: {}: {}", path, err);
+                return ExitCode::FAILURE;
+            }
+        },
+        Err(err) => {
+            eprintln!("error: could not read {}: {}", path, err);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let start = Instant::now();
+    let mut mismatches = 0;
+    for entry in &entries {
+        if let Some(mismatch) = entry.check(max_depth, &count) {
+            println!("{}", mismatch);
+            mismatches += 1;
+        }
+    }
+    println!(
+        "{} positions, {} mismatches up to depth {} in {} ms",
+        entries.len(),
+        mismatches,
+        max_depth,
+        start.elapsed().as_millis()
+    );
+    if mismatches == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
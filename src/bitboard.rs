@@ -226,7 +226,48 @@ impl BitBoard {
         BitBoard::from_bits(swap4)
     }
 
+    /// The set, plus every square north of it.
+    pub const fn north_fill(self) -> BitBoard {
+        let mut bits = self.bits;
+        bits |= bits << 8;
+        bits |= bits << 16;
+        bits |= bits << 32;
+        BitBoard::from_bits(bits)
+    }
+    /// The set, plus every square south of it.
+    pub const fn south_fill(self) -> BitBoard {
+        let mut bits = self.bits;
+        bits |= bits >> 8;
+        bits |= bits >> 16;
+        bits |= bits >> 32;
+        BitBoard::from_bits(bits)
+    }
+    /// Every file that has a square in the set.
+    pub const fn file_fill(self) -> BitBoard {
+        self.north_fill().union(self.south_fill())
+    }
+    /// Every square strictly north of the set, like the path of a white pawn.
+    pub const fn north_span(self) -> BitBoard {
+        BitBoard::from_bits(self.north_fill().bits << 8)
+    }
+    /// Every square strictly south of the set, like the path of a black pawn.
+    pub const fn south_span(self) -> BitBoard {
+        BitBoard::from_bits(self.south_fill().bits >> 8)
+    }
+    /// The squares directly east and west of the set. Unlike [`BitBoard::east`] and
+    /// [`BitBoard::west`], squares on the rim are allowed, and just have one neighbour.
+    pub const fn sideways(self) -> BitBoard {
+        BitBoard::from_bits(
+            ((self.bits << 1) & !BitBoard::FA.bits) | ((self.bits >> 1) & !BitBoard::FH.bits),
+        )
+    }
+    /// The files next to the files of the set.
+    pub const fn adjacent_files(self) -> BitBoard {
+        self.file_fill().sideways()
+    }
+
     pub const EMPTY: BitBoard = BitBoard { bits: 0 };
+    pub const FULL: BitBoard = BitBoard { bits: !0 };
     pub const R1: BitBoard = Rank::R1.to_bitboard();
     pub const R2: BitBoard = Rank::R2.to_bitboard();
     pub const R7: BitBoard = Rank::R7.to_bitboard();
@@ -302,6 +343,29 @@ mod tests {
         sq.to_bitboard() == BitBoard::from_squares([sq].into_iter())
     }
 
+    #[quickcheck]
+    fn spans_split_the_file(sq: Square) -> bool {
+        let bb = sq.to_bitboard();
+        let (_, file) = sq.to_coord();
+        let file = file.to_bitboard();
+        bb.file_fill() == file
+            && bb.north_span().union(bb).union(bb.south_span()) == file
+            && !bb.north_span().intersects(bb.south_span())
+            && bb.north_fill() == bb.union(bb.north_span())
+            && bb.south_fill() == bb.union(bb.south_span())
+    }
+
+    #[quickcheck]
+    fn sideways_neighbours(sq: Square) -> bool {
+        let bb = sq.to_bitboard();
+        let on_rim = BitBoard::FA.union(BitBoard::FH).contains(sq);
+        let expected = if on_rim { 1 } else { 2 };
+        bb.sideways().popcount() == expected
+            && sq.to_coord().0.to_bitboard().is_supserset_of(bb.sideways())
+            && bb.adjacent_files().popcount() == 8 * expected
+            && !bb.adjacent_files().intersects(bb.file_fill())
+    }
+
     #[quickcheck]
     fn set_get(bb: BitBoard, sq: Square, val: bool) -> bool {
         bb.set_to(sq, val).contains(sq) == val
@@ -0,0 +1,507 @@
+use std::{collections::HashMap, io, path::Path};
+
+use tinyrand::Rand;
+
+use crate::{
+    coord::{File, Rank, Square},
+    moves::Move,
+    perft::node::Node,
+    pgn::{GameResult, PgnError, PgnGame},
+    piece::{PieceType, Side},
+};
+
+/// A single 16-byte record of a Polyglot `.bin` book, stored big-endian on disk.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct BookEntry {
+    pub key: u64,
+    /// Move in Polyglot's encoding, see [`decode_move`]
+    pub raw_move: u16,
+    pub weight: u16,
+    pub learn: u32,
+}
+
+impl BookEntry {
+    pub const SIZE: usize = 16;
+
+    pub fn from_bytes(bytes: &[u8; Self::SIZE]) -> Self {
+        BookEntry {
+            key: u64::from_be_bytes(bytes[0..8].try_into().unwrap()),
+            raw_move: u16::from_be_bytes(bytes[8..10].try_into().unwrap()),
+            weight: u16::from_be_bytes(bytes[10..12].try_into().unwrap()),
+            learn: u32::from_be_bytes(bytes[12..16].try_into().unwrap()),
+        }
+    }
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0; Self::SIZE];
+        bytes[0..8].copy_from_slice(&self.key.to_be_bytes());
+        bytes[8..10].copy_from_slice(&self.raw_move.to_be_bytes());
+        bytes[10..12].copy_from_slice(&self.weight.to_be_bytes());
+        bytes[12..16].copy_from_slice(&self.learn.to_be_bytes());
+        bytes
+    }
+}
+
+#[derive(Debug)]
+pub enum BookError {
+    Io(io::Error),
+    /// The file size is not a multiple of [`BookEntry::SIZE`]
+    BadLength(usize),
+    /// Entries have to be sorted by key for lookups to work
+    Unsorted {
+        index: usize,
+    },
+}
+
+impl std::fmt::Display for BookError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BookError::Io(err) => write!(f, "could not read book: {}", err),
+            BookError::BadLength(len) => write!(
+                f,
+                "book size of {} bytes is not a multiple of {}",
+                len,
+                BookEntry::SIZE
+            ),
+            BookError::Unsorted { index } => write!(f, "book entry {} is out of order", index),
+        }
+    }
+}
+
+impl std::error::Error for BookError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BookError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for BookError {
+    fn from(err: io::Error) -> Self {
+        BookError::Io(err)
+    }
+}
+
+/// An opening book in the Polyglot format, read into memory in full.
+/// Positions are looked up by [`Node::polyglot_key`].
+pub struct Book {
+    entries: Vec<BookEntry>,
+}
+
+impl Book {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Book, BookError> {
+        Self::from_bytes(&std::fs::read(path)?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Book, BookError> {
+        let (chunks, rest) = bytes.as_chunks::<{ BookEntry::SIZE }>();
+        if !rest.is_empty() {
+            return Err(BookError::BadLength(bytes.len()));
+        }
+        Self::from_entries(chunks.iter().map(BookEntry::from_bytes).collect())
+    }
+
+    pub fn from_entries(entries: Vec<BookEntry>) -> Result<Book, BookError> {
+        if let Some(index) = entries
+            .windows(2)
+            .position(|pair| pair[0].key > pair[1].key)
+        {
+            return Err(BookError::Unsorted { index: index + 1 });
+        }
+        Ok(Book { entries })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.entries.iter().flat_map(BookEntry::to_bytes).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// All entries for `key`, found by binary search.
+    pub fn entries(&self, key: u64) -> &[BookEntry] {
+        let start = self.entries.partition_point(|entry| entry.key < key);
+        let len = self.entries[start..].partition_point(|entry| entry.key == key);
+        &self.entries[start..start + len]
+    }
+
+    /// The legal book moves in this position, with their weights.
+    /// Entries that don't decode to a legal move, e.g. because of a key collision, are skipped.
+    pub fn moves(&self, node: &Node) -> Vec<(Move, u16)> {
+        self.entries(node.polyglot_key())
+            .iter()
+            .filter_map(|entry| Some((decode_move(node, entry.raw_move)?, entry.weight)))
+            .collect()
+    }
+
+    /// The book move with the highest weight, if any.
+    pub fn best_move(&self, node: &Node) -> Option<Move> {
+        self.moves(node)
+            .into_iter()
+            .filter(|&(_, weight)| weight > 0)
+            .max_by_key(|&(_, weight)| weight)
+            .map(|(mv, _)| mv)
+    }
+
+    /// A random book move, with probability proportional to its weight.
+    /// Moves with weight 0 are never picked.
+    pub fn weighted_move<R: Rand>(&self, node: &Node, rng: &mut R) -> Option<Move> {
+        let moves = self.moves(node);
+        let total: u32 = moves.iter().map(|&(_, weight)| weight as u32).sum();
+        if total == 0 {
+            return None;
+        }
+        let mut pick = rng.next_lim_u32(total);
+        for (mv, weight) in moves {
+            if pick < weight as u32 {
+                return Some(mv);
+            }
+            pick -= weight as u32;
+        }
+        unreachable!()
+    }
+}
+
+/// Win/draw/loss counts of a book move, from the perspective of the side that played it.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct MoveStats {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+impl MoveStats {
+    pub const fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+    /// Polyglot's usual weighting, which scores a win as two draws, and a loss as nothing.
+    pub const fn weight(&self) -> u32 {
+        2 * self.wins + self.draws
+    }
+}
+
+/// Collects move statistics from games, and turns them into a [`Book`].
+pub struct BookBuilder {
+    max_ply: usize,
+    min_games: u32,
+    stats: HashMap<(u64, u16), MoveStats>,
+}
+
+impl BookBuilder {
+    /// Only the first `max_ply` moves of every game are included, and moves have to be played in
+    /// at least `min_games` games to make it into the book.
+    pub fn new(max_ply: usize, min_games: u32) -> Self {
+        BookBuilder {
+            max_ply,
+            min_games,
+            stats: HashMap::new(),
+        }
+    }
+
+    /// Adds the moves of `game`. Games without a result are skipped, since they don't say anything
+    /// about the quality of the moves.
+    /// If the game contains an invalid move, the moves up to that point are still added.
+    pub fn add_game(&mut self, game: &PgnGame) -> Result<(), PgnError> {
+        if game.result == GameResult::Unknown {
+            return Ok(());
+        }
+        let opening = PgnGame {
+            moves: game.moves.iter().take(self.max_ply).cloned().collect(),
+            ..game.clone()
+        };
+        opening.replay(|node, mv| {
+            let stats = self
+                .stats
+                .entry((node.polyglot_key(), encode_move(mv)))
+                .or_default();
+            match (game.result, node.side) {
+                (GameResult::Draw, _) => stats.draws += 1,
+                (GameResult::WhiteWins, Side::White) | (GameResult::BlackWins, Side::Black) => {
+                    stats.wins += 1
+                }
+                _ => stats.losses += 1,
+            }
+        })
+    }
+
+    /// The statistics collected for the move `raw_move` in the position with `key`.
+    pub fn stats(&self, key: u64, raw_move: u16) -> Option<MoveStats> {
+        self.stats.get(&(key, raw_move)).copied()
+    }
+
+    /// Builds the book. Moves that were never won or drawn get weight 0 and are left out, and
+    /// weights are scaled down per position where needed to fit in 16 bits.
+    pub fn build(&self) -> Book {
+        let mut positions: HashMap<u64, Vec<(u16, u32)>> = HashMap::new();
+        for (&(key, raw_move), stats) in &self.stats {
+            if stats.games() >= self.min_games && stats.weight() > 0 {
+                positions
+                    .entry(key)
+                    .or_default()
+                    .push((raw_move, stats.weight()));
+            }
+        }
+
+        let mut entries = Vec::new();
+        for (key, moves) in positions {
+            let max = moves.iter().map(|&(_, weight)| weight).max().unwrap_or(0);
+            let scale = |weight: u32| {
+                if max <= u16::MAX as u32 {
+                    weight as u16
+                } else {
+                    ((weight as u64 * u16::MAX as u64 / max as u64) as u16).max(1)
+                }
+            };
+            entries.extend(moves.into_iter().map(|(raw_move, weight)| BookEntry {
+                key,
+                raw_move,
+                weight: scale(weight),
+                learn: 0,
+            }));
+        }
+        entries.sort_by(|a, b| {
+            (a.key, std::cmp::Reverse(a.weight), a.raw_move).cmp(&(
+                b.key,
+                std::cmp::Reverse(b.weight),
+                b.raw_move,
+            ))
+        });
+        Book { entries }
+    }
+}
+
+// Polyglot's piece encoding for promotions, 0 meaning no promotion
+const fn promotion_index(promotion: Option<PieceType>) -> u16 {
+    match promotion {
+        None => 0,
+        Some(PieceType::Knight) => 1,
+        Some(PieceType::Bishop) => 2,
+        Some(PieceType::Rook) => 3,
+        Some(PieceType::Queen) => 4,
+        Some(_) => panic!("Invalid promotion"),
+    }
+}
+
+// Castling is encoded as the king capturing its own rook
+const fn castling_king_target(mv: Move) -> Square {
+    match mv.to() {
+        Square::G1 => Square::H1,
+        Square::C1 => Square::A1,
+        Square::G8 => Square::H8,
+        Square::C8 => Square::A8,
+        _ => panic!("Not a castling move"),
+    }
+}
+
+/// Encodes `mv` the way Polyglot books store it:
+///   bits  0-2: destination file
+///   bits  3-5: destination rank
+///   bits  6-8: source file
+///   bits 9-11: source rank
+///   bits 12-14: promotion piece
+pub const fn encode_move(mv: Move) -> u16 {
+    let to = if mv.is_castle() {
+        castling_king_target(mv)
+    } else {
+        mv.to()
+    };
+    to.to_index() as u16
+        | (mv.from().to_index() as u16) << 6
+        | promotion_index(mv.promotion()) << 12
+}
+
+/// Decodes a Polyglot move by finding the matching legal move in `node`, since the encoding lacks
+/// the information to tell e.g. captures or castling apart by itself.
+pub fn decode_move(node: &Node, raw: u16) -> Option<Move> {
+    let square = |bits: u16| {
+        Square::from_coord(
+            File::from_index((bits & 7) as u8).unwrap(),
+            Rank::from_index((bits >> 3 & 7) as u8).unwrap(),
+        )
+    };
+    let from = square(raw >> 6);
+    let to = square(raw);
+    let promotion = raw >> 12 & 7;
+    node.legal_moves().iter().copied().find(|&mv| {
+        mv.from() == from
+            && (mv.to() == to || (mv.is_castle() && castling_king_target(mv) == to))
+            && promotion_index(mv.promotion()) == promotion
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use tinyrand::{Seeded, StdRand};
+
+    use crate::{
+        book::{Book, BookBuilder, BookEntry, BookError, MoveStats, decode_move, encode_move},
+        coord::Square,
+        moves::{Move, MoveType},
+        perft::node::Node,
+        pgn::parse_pgn,
+        position::Position,
+    };
+
+    fn entry(node: &Node, mv: Move, weight: u16) -> BookEntry {
+        BookEntry {
+            key: node.polyglot_key(),
+            raw_move: encode_move(mv),
+            weight,
+            learn: 0,
+        }
+    }
+
+    #[test]
+    fn move_encoding() {
+        // Examples from the format specification
+        let e2e4 = Move::new(Square::E2, Square::E4, MoveType::DoublePush);
+        assert_eq!(encode_move(e2e4), 0x031c);
+        let castle = Move::new(Square::E1, Square::G1, MoveType::CastleKingside);
+        assert_eq!(
+            encode_move(castle),
+            encode_move(Move::new(Square::E1, Square::H1, MoveType::Quiet))
+        );
+        let promo = Move::new(Square::B7, Square::A8, MoveType::PromoteCaptureRook);
+        assert_eq!(encode_move(promo) >> 12, 3);
+
+        for node in [
+            Node::POSITION_2,
+            Node::POSITION_3,
+            Node::POSITION_4,
+            Node::POSITION_5,
+        ] {
+            for &mv in &node.legal_moves() {
+                assert_eq!(decode_move(&node, encode_move(mv)), Some(mv));
+            }
+        }
+        // Not legal in the start position
+        assert_eq!(decode_move(&Node::POSITION_1, encode_move(castle)), None);
+    }
+
+    #[test]
+    fn lookup() {
+        let start = Node::POSITION_1;
+        let e4 = Move::new(Square::E2, Square::E4, MoveType::DoublePush);
+        let d4 = Move::new(Square::D2, Square::D4, MoveType::DoublePush);
+        let h4 = Move::new(Square::H2, Square::H4, MoveType::DoublePush);
+        let after_e4 = start.make_move(e4);
+        let e5 = Move::new(Square::E7, Square::E5, MoveType::DoublePush);
+
+        let mut entries = vec![
+            entry(&start, e4, 10),
+            entry(&start, d4, 5),
+            entry(&start, h4, 0),
+            entry(&after_e4, e5, 1),
+        ];
+        entries.sort_by_key(|entry| entry.key);
+        let book = Book::from_entries(entries).unwrap();
+        let book = Book::from_bytes(&book.to_bytes()).unwrap();
+
+        assert_eq!(book.len(), 4);
+        assert_eq!(book.moves(&start).len(), 3);
+        assert_eq!(book.best_move(&start), Some(e4));
+        assert_eq!(book.best_move(&after_e4), Some(e5));
+        assert_eq!(book.best_move(&after_e4.make_move(e5)), None);
+
+        let mut rng = StdRand::seed(0x5eed);
+        let picks: Vec<Move> = (0..100)
+            .map(|_| book.weighted_move(&start, &mut rng).unwrap())
+            .collect();
+        assert!(picks.contains(&e4));
+        assert!(picks.contains(&d4));
+        assert!(!picks.contains(&h4));
+    }
+
+    #[test]
+    fn castling_entry() {
+        let node = Position::parse_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1")
+            .unwrap()
+            .to_node();
+        let raw = encode_move(Move::new(Square::E1, Square::A1, MoveType::Quiet));
+        assert_eq!(
+            decode_move(&node, raw),
+            Some(Move::new(Square::E1, Square::C1, MoveType::CastleQueenside))
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert!(matches!(
+            Book::from_bytes(&[0; 17]),
+            Err(BookError::BadLength(17))
+        ));
+        let entry = |key| BookEntry {
+            key,
+            raw_move: 0,
+            weight: 1,
+            learn: 0,
+        };
+        let bytes: Vec<u8> = [entry(2), entry(1)]
+            .iter()
+            .flat_map(BookEntry::to_bytes)
+            .collect();
+        assert!(matches!(
+            Book::from_bytes(&bytes),
+            Err(BookError::Unsorted { index: 1 })
+        ));
+        assert!(matches!(
+            Book::open("/nonexistent/book.bin"),
+            Err(BookError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn builder() {
+        let games = parse_pgn(
+            "1. e4 e5 2. Nf3 1-0\n1. e4 c5 0-1\n1. d4 d5 1/2-1/2\n1. e4 e5 *\n1. e4 Ke7 Ke2 1-0",
+        );
+        let mut builder = BookBuilder::new(2, 1);
+        for game in &games[..4] {
+            builder.add_game(game).unwrap();
+        }
+        // The moves before the illegal one still count
+        assert!(builder.add_game(&games[4]).is_err());
+
+        let start = Node::POSITION_1;
+        let e4 = Move::new(Square::E2, Square::E4, MoveType::DoublePush);
+        let d4 = Move::new(Square::D2, Square::D4, MoveType::DoublePush);
+        let after_e4 = start.make_move(e4);
+        let e5 = Move::new(Square::E7, Square::E5, MoveType::DoublePush);
+        let c5 = Move::new(Square::C7, Square::C5, MoveType::DoublePush);
+        assert_eq!(
+            builder.stats(start.polyglot_key(), encode_move(e4)),
+            Some(MoveStats {
+                wins: 2,
+                draws: 0,
+                losses: 1
+            })
+        );
+        assert_eq!(
+            builder.stats(after_e4.polyglot_key(), encode_move(e5)),
+            Some(MoveStats {
+                wins: 0,
+                draws: 0,
+                losses: 1
+            })
+        );
+
+        let book = builder.build();
+        assert_eq!(book.moves(&start), [(e4, 4), (d4, 1)]);
+        // Lost every game, so it's left out
+        assert_eq!(book.moves(&after_e4), [(c5, 2)]);
+        // Beyond the maximum ply
+        assert!(book.moves(&after_e4.make_move(e5)).is_empty());
+        assert_eq!(book.len(), 4);
+
+        let mut builder = BookBuilder::new(2, 2);
+        for game in &games[..4] {
+            builder.add_game(game).unwrap();
+        }
+        assert_eq!(builder.build().moves(&start), [(e4, 2)]);
+    }
+}
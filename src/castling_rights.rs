@@ -1,4 +1,4 @@
-use crate::piece::Side;
+use crate::{coord::Square, piece::Side};
 
 // TODO check if just having 4 bools is faster.
 #[derive(Clone, Copy, PartialEq, Eq)]
@@ -6,6 +6,7 @@ pub struct CastlingRights {
     bitset: u8,
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum CastlingSide {
     KingSide = 0,
     QueenSide = 1,
@@ -25,7 +26,7 @@ impl CastlingRights {
     }
 
     pub const fn can_castle(&self, side: Side, castling_side: CastlingSide) -> bool {
-        self.bitset & Self::mask(side, castling_side) == 0
+        self.bitset & Self::mask(side, castling_side) != 0
     }
 
     pub const fn revoke(&mut self, side: Side, castling_side: CastlingSide) {
@@ -36,9 +37,64 @@ impl CastlingRights {
         self.bitset |= Self::mask(side, castling_side)
     }
 
+    /// Revokes any rights that are lost by a move from `from` to `to`, i.e. when a king or rook
+    /// moves away from its starting square, or a rook gets captured on it.
+    pub const fn revoke_for_move(&mut self, from: Square, to: Square) {
+        const fn lost_rights(sq: Square) -> u8 {
+            use CastlingSide::*;
+            use Side::*;
+            match sq {
+                Square::E1 => {
+                    CastlingRights::mask(White, KingSide) | CastlingRights::mask(White, QueenSide)
+                }
+                Square::H1 => CastlingRights::mask(White, KingSide),
+                Square::A1 => CastlingRights::mask(White, QueenSide),
+                Square::E8 => {
+                    CastlingRights::mask(Black, KingSide) | CastlingRights::mask(Black, QueenSide)
+                }
+                Square::H8 => CastlingRights::mask(Black, KingSide),
+                Square::A8 => CastlingRights::mask(Black, QueenSide),
+                _ => 0,
+            }
+        }
+        self.bitset &= !(lost_rights(from) | lost_rights(to));
+    }
+
     /// 0-16
     /// For Zobrist purposes
     pub const fn to_index(&self) -> u8 {
         self.bitset
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{coord::Square, piece::Side};
+
+    use super::{CastlingRights, CastlingSide};
+
+    const ALL: [(Side, CastlingSide); 4] = [
+        (Side::White, CastlingSide::KingSide),
+        (Side::White, CastlingSide::QueenSide),
+        (Side::Black, CastlingSide::KingSide),
+        (Side::Black, CastlingSide::QueenSide),
+    ];
+
+    #[test]
+    fn can_castle() {
+        for (side, castling_side) in ALL {
+            assert!(CastlingRights::new_full().can_castle(side, castling_side));
+            assert!(!CastlingRights::new_empty().can_castle(side, castling_side));
+        }
+
+        let mut rights = CastlingRights::new_full();
+        rights.revoke(Side::White, CastlingSide::QueenSide);
+        assert!(!rights.can_castle(Side::White, CastlingSide::QueenSide));
+        assert!(rights.can_castle(Side::White, CastlingSide::KingSide));
+        rights.revoke_for_move(Square::E8, Square::E7);
+        assert!(!rights.can_castle(Side::Black, CastlingSide::KingSide));
+        assert!(!rights.can_castle(Side::Black, CastlingSide::QueenSide));
+        rights.restore(Side::White, CastlingSide::QueenSide);
+        assert!(rights.can_castle(Side::White, CastlingSide::QueenSide));
+    }
+}
@@ -0,0 +1,109 @@
+use crate::{
+    perft::node::Node,
+    piece::{Piece, PieceType, Side},
+    pieces::{
+        bishop::bishop_moves, king::king_moves, knight::knight_moves, queen::queen_moves,
+        rook::rook_moves,
+    },
+};
+
+use super::Tapered;
+
+// Per square of the king zone an enemy piece attacks
+const ATTACK_WEIGHTS: [(PieceType, i32); 4] = [
+    (PieceType::Knight, 20),
+    (PieceType::Bishop, 20),
+    (PieceType::Rook, 40),
+    (PieceType::Queen, 80),
+];
+// Percentage of the attack weight that counts, by the number of attacking pieces. A lone
+// attacker rarely gets anywhere.
+const ATTACKER_SCALE: [i32; 8] = [0, 0, 50, 75, 88, 94, 97, 99];
+// Per pawn directly in front of the king or on the adjacent files, one and two ranks ahead
+const SHIELD: [Tapered; 2] = [Tapered::new(12, 0), Tapered::new(6, 0)];
+// Per file next to or on the king's file without any pawns of its own, and without any pawns
+const SEMI_OPEN_FILE: Tapered = Tapered::new(-15, 0);
+const OPEN_FILE: Tapered = Tapered::new(-25, 0);
+
+/// The king safety term for `side`, from its own point of view: the enemy pieces bearing down on
+/// the squares around its king, the pawns sheltering it, and the open files leading to it. It
+/// only matters with pieces on the board, so there's no endgame part.
+pub fn king_safety(node: &Node, side: Side) -> Tapered {
+    let king = node.king_square(side);
+    let zone = king_moves(king).set(king);
+
+    let mut attackers = 0;
+    let mut weight = 0;
+    for (piece_type, piece_weight) in ATTACK_WEIGHTS {
+        for (sq, _) in node.piece(Piece::from_side_piece(side.opponent(), piece_type)) {
+            let attacks = match piece_type {
+                PieceType::Knight => knight_moves(sq),
+                PieceType::Bishop => bishop_moves(sq, node.occupancy_total),
+                PieceType::Rook => rook_moves(sq, node.occupancy_total),
+                _ => queen_moves(sq, node.occupancy_total),
+            };
+            let attacked = attacks.intersect(zone).popcount() as i32;
+            if attacked > 0 {
+                attackers += 1;
+                weight += piece_weight * attacked;
+            }
+        }
+    }
+    let danger = weight * ATTACKER_SCALE[attackers.min(ATTACKER_SCALE.len() - 1)] / 100;
+    let mut score = Tapered::new(-danger, 0);
+
+    let own_pawns = node.piece(Piece::from_side_piece(side, PieceType::Pawn));
+    let all_pawns =
+        own_pawns.union(node.piece(Piece::from_side_piece(side.opponent(), PieceType::Pawn)));
+    let files = king.to_bitboard().union(king.to_bitboard().sideways());
+    let ahead = |ranks: i8| match side {
+        Side::White => files.lshift(8 * ranks),
+        Side::Black => files.rshift(8 * ranks),
+    };
+    score += SHIELD[0] * own_pawns.intersect(ahead(1)).popcount() as i32;
+    score += SHIELD[1] * own_pawns.intersect(ahead(2)).popcount() as i32;
+
+    for (_, bb) in files {
+        let file = bb.file_fill();
+        if !all_pawns.intersects(file) {
+            score += OPEN_FILE;
+        } else if !own_pawns.intersects(file) {
+            score += SEMI_OPEN_FILE;
+        }
+    }
+    score
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        eval::king_safety::king_safety, perft::node::Node, piece::Side, position::Position,
+    };
+
+    fn safety(fen: &str, side: Side) -> i32 {
+        king_safety(&Position::parse_fen(fen).unwrap().to_node(), side).mg
+    }
+
+    #[test]
+    fn king_safety_terms() {
+        assert_eq!(
+            king_safety(&Node::POSITION_1, Side::White),
+            king_safety(&Node::POSITION_1, Side::Black)
+        );
+        // Pawns in front of the king
+        let sheltered = safety("6k1/5ppp/8/8/8/8/5PPP/6K1 w - - 0 1", Side::White);
+        let advanced = safety("6k1/5ppp/8/8/8/5PPP/8/6K1 w - - 0 1", Side::White);
+        let missing = safety("6k1/5ppp/8/8/8/8/5P1P/6K1 w - - 0 1", Side::White);
+        assert!(sheltered > advanced);
+        assert!(advanced > missing);
+        // The g-file is fully open here
+        assert!(missing > safety("6k1/5p1p/8/8/8/8/5P1P/6K1 w - - 0 1", Side::White));
+
+        // One attacker is tolerated, two start to hurt
+        let castled = "r4rk1/5ppp/8/8/8/8/5PPP/6K1 b - - 0 1";
+        let one = "r4rk1/5ppp/8/6N1/8/8/5PPP/6K1 b - - 0 1";
+        let two = "r4rk1/5ppp/8/6NQ/8/8/5PPP/6K1 b - - 0 1";
+        assert_eq!(safety(one, Side::Black), safety(castled, Side::Black));
+        assert!(safety(two, Side::Black) < safety(one, Side::Black));
+    }
+}
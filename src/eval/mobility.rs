@@ -0,0 +1,89 @@
+use crate::{
+    bitboard::BitBoard,
+    perft::node::Node,
+    piece::{Piece, PieceType, Side},
+    pieces::{bishop::bishop_moves, knight::knight_moves, queen::queen_moves, rook::rook_moves},
+};
+
+use super::Tapered;
+
+// Per safe square, and the number of safe squares a piece of that type typically has, which
+// scores 0. Rooks and queens are slow to get going, their mobility matters more later on.
+const KNIGHT: (Tapered, i32) = (Tapered::new(4, 4), 4);
+const BISHOP: (Tapered, i32) = (Tapered::new(5, 5), 6);
+const ROOK: (Tapered, i32) = (Tapered::new(2, 4), 6);
+const QUEEN: (Tapered, i32) = (Tapered::new(1, 2), 12);
+
+/// The squares the pawns of `side` attack.
+fn pawn_attack_set(node: &Node, side: Side) -> BitBoard {
+    let pawns = node.piece(Piece::from_side_piece(side, PieceType::Pawn));
+    match side {
+        Side::White => pawns.sideways().lshift(8),
+        Side::Black => pawns.sideways().rshift(8),
+    }
+}
+
+/// The mobility term for `side`, from its own point of view. Only the squares that aren't
+/// occupied by its own pieces or attacked by enemy pawns count.
+pub fn mobility(node: &Node, side: Side) -> Tapered {
+    let safe = node
+        .occupancy(side)
+        .union(pawn_attack_set(node, side.opponent()))
+        .complement();
+    let score = |piece_type, moves: usize, (weight, baseline): (Tapered, i32)| {
+        let count = node
+            .piece(Piece::from_side_piece(side, piece_type))
+            .popcount() as i32;
+        weight * (moves as i32 - baseline * count)
+    };
+    let piece = |piece_type| Piece::from_side_piece(side, piece_type);
+    score(
+        PieceType::Knight,
+        node.count_jumper_moves(piece(PieceType::Knight), safe, knight_moves),
+        KNIGHT,
+    ) + score(
+        PieceType::Bishop,
+        node.count_slider_moves(piece(PieceType::Bishop), safe, bishop_moves),
+        BISHOP,
+    ) + score(
+        PieceType::Rook,
+        node.count_slider_moves(piece(PieceType::Rook), safe, rook_moves),
+        ROOK,
+    ) + score(
+        PieceType::Queen,
+        node.count_slider_moves(piece(PieceType::Queen), safe, queen_moves),
+        QUEEN,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{eval::mobility::mobility, perft::node::Node, piece::Side, position::Position};
+
+    fn node(fen: &str) -> Node {
+        Position::parse_fen(fen).unwrap().to_node()
+    }
+
+    #[test]
+    fn safe_squares() {
+        assert_eq!(
+            mobility(&Node::POSITION_1, Side::White),
+            mobility(&Node::POSITION_1, Side::Black)
+        );
+        // A centralized knight beats a cornered one
+        let center = node("4k3/8/8/8/3N4/8/8/4K3 w - - 0 1");
+        let corner = node("4k3/8/8/8/8/8/8/N3K3 w - - 0 1");
+        assert!(mobility(&center, Side::White).mg > mobility(&corner, Side::White).mg);
+        // Squares covered by enemy pawns don't count, but squares with enemy pieces do
+        let covered = node("4k3/8/2p1p3/8/3N4/8/8/4K3 w - - 0 1");
+        let targets = node("4k3/8/2n1n3/8/3N4/8/8/4K3 w - - 0 1");
+        assert_eq!(
+            mobility(&covered, Side::White),
+            mobility(&center, Side::White) - super::KNIGHT.0 * 2
+        );
+        assert_eq!(
+            mobility(&targets, Side::White),
+            mobility(&center, Side::White)
+        );
+    }
+}
@@ -0,0 +1,536 @@
+mod king_safety;
+mod mobility;
+mod pawns;
+
+use std::{
+    fmt,
+    ops::{Add, AddAssign, Mul, Neg, Sub},
+};
+
+use crate::{
+    perft::node::Node,
+    piece::{Piece, PieceType, Side},
+};
+
+pub use king_safety::king_safety;
+pub use mobility::mobility;
+pub use pawns::{PawnFeatures, PawnTable, pawn_structure};
+
+/// A middlegame and an endgame score, blended by [`Phase`] at the end of the evaluation.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Tapered {
+    pub mg: i32,
+    pub eg: i32,
+}
+
+impl Tapered {
+    pub const ZERO: Tapered = Tapered::new(0, 0);
+    pub const fn new(mg: i32, eg: i32) -> Self {
+        Tapered { mg, eg }
+    }
+    pub const fn blend(self, phase: Phase) -> i32 {
+        (self.mg * phase.0 + self.eg * (Phase::MAX - phase.0)) / Phase::MAX
+    }
+}
+
+impl Add for Tapered {
+    type Output = Tapered;
+    fn add(self, rhs: Tapered) -> Tapered {
+        Tapered::new(self.mg + rhs.mg, self.eg + rhs.eg)
+    }
+}
+
+impl AddAssign for Tapered {
+    fn add_assign(&mut self, rhs: Tapered) {
+        *self = *self + rhs;
+    }
+}
+
+impl Sub for Tapered {
+    type Output = Tapered;
+    fn sub(self, rhs: Tapered) -> Tapered {
+        Tapered::new(self.mg - rhs.mg, self.eg - rhs.eg)
+    }
+}
+
+impl Neg for Tapered {
+    type Output = Tapered;
+    fn neg(self) -> Tapered {
+        Tapered::new(-self.mg, -self.eg)
+    }
+}
+
+impl Mul<i32> for Tapered {
+    type Output = Tapered;
+    fn mul(self, rhs: i32) -> Tapered {
+        Tapered::new(self.mg * rhs, self.eg * rhs)
+    }
+}
+
+/// How far the game is from the endgame, based on the non-pawn material left on the board.
+/// [`Phase::MAX`] with all pieces still on the board, 0 with only kings and pawns.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Phase(pub i32);
+
+impl Phase {
+    pub const MAX: i32 = 24;
+
+    pub fn of(node: &Node) -> Phase {
+        let phase = [
+            (PieceType::Knight, 1),
+            (PieceType::Bishop, 1),
+            (PieceType::Rook, 2),
+            (PieceType::Queen, 4),
+        ]
+        .into_iter()
+        .map(|(piece_type, weight)| {
+            let count = node
+                .piece(Piece::from_side_piece(Side::White, piece_type))
+                .popcount()
+                + node
+                    .piece(Piece::from_side_piece(Side::Black, piece_type))
+                    .popcount();
+            weight * count as i32
+        })
+        .sum::<i32>();
+        // Promotions can push it over the maximum
+        Phase(phase.min(Self::MAX))
+    }
+}
+
+/// The parts the evaluation is made up of, see [`Breakdown`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Term {
+    Material,
+    PieceSquares,
+    BishopPair,
+    Pawns,
+    Mobility,
+    KingSafety,
+    Tempo,
+}
+
+impl Term {
+    pub const COUNT: usize = 7;
+    pub const ALL: [Term; Self::COUNT] = [
+        Term::Material,
+        Term::PieceSquares,
+        Term::BishopPair,
+        Term::Pawns,
+        Term::Mobility,
+        Term::KingSafety,
+        Term::Tempo,
+    ];
+    pub const fn name(self) -> &'static str {
+        match self {
+            Term::Material => "Material",
+            Term::PieceSquares => "Piece squares",
+            Term::BishopPair => "Bishop pair",
+            Term::Pawns => "Pawns",
+            Term::Mobility => "Mobility",
+            Term::KingSafety => "King safety",
+            Term::Tempo => "Tempo",
+        }
+    }
+}
+
+const PIECE_TYPES: [PieceType; 6] = [
+    PieceType::Pawn,
+    PieceType::Knight,
+    PieceType::Bishop,
+    PieceType::Rook,
+    PieceType::Queen,
+    PieceType::King,
+];
+
+// Indexed by piece type
+const MATERIAL: [Tapered; 6] = [
+    Tapered::new(82, 94),
+    Tapered::new(337, 281),
+    Tapered::new(365, 297),
+    Tapered::new(477, 512),
+    Tapered::new(1025, 936),
+    Tapered::new(0, 0),
+];
+const BISHOP_PAIR: Tapered = Tapered::new(30, 50);
+const TEMPO: Tapered = Tapered::new(15, 5);
+
+// Piece-square tables from White's point of view, laid out like a board diagram: a8 first, h1
+// last. The pieces other than pawns and the king use the same table in both phases.
+#[rustfmt::skip]
+const PAWN_MG: [i32; 64] = [
+     0,   0,   0,   0,   0,   0,   0,   0,
+    50,  50,  50,  50,  50,  50,  50,  50,
+    10,  10,  20,  30,  30,  20,  10,  10,
+     5,   5,  10,  25,  25,  10,   5,   5,
+     0,   0,   0,  20,  20,   0,   0,   0,
+     5,  -5, -10,   0,   0, -10,  -5,   5,
+     5,  10,  10, -20, -20,  10,  10,   5,
+     0,   0,   0,   0,   0,   0,   0,   0,
+];
+#[rustfmt::skip]
+const PAWN_EG: [i32; 64] = [
+     0,   0,   0,   0,   0,   0,   0,   0,
+    80,  80,  80,  80,  80,  80,  80,  80,
+    50,  50,  50,  50,  50,  50,  50,  50,
+    30,  30,  30,  30,  30,  30,  30,  30,
+    15,  15,  15,  15,  15,  15,  15,  15,
+     5,   5,   5,   5,   5,   5,   5,   5,
+     0,   0,   0,   0,   0,   0,   0,   0,
+     0,   0,   0,   0,   0,   0,   0,   0,
+];
+#[rustfmt::skip]
+const KNIGHT: [i32; 64] = [
+   -50, -40, -30, -30, -30, -30, -40, -50,
+   -40, -20,   0,   0,   0,   0, -20, -40,
+   -30,   0,  10,  15,  15,  10,   0, -30,
+   -30,   5,  15,  20,  20,  15,   5, -30,
+   -30,   0,  15,  20,  20,  15,   0, -30,
+   -30,   5,  10,  15,  15,  10,   5, -30,
+   -40, -20,   0,   5,   5,   0, -20, -40,
+   -50, -40, -30, -30, -30, -30, -40, -50,
+];
+#[rustfmt::skip]
+const BISHOP: [i32; 64] = [
+   -20, -10, -10, -10, -10, -10, -10, -20,
+   -10,   0,   0,   0,   0,   0,   0, -10,
+   -10,   0,   5,  10,  10,   5,   0, -10,
+   -10,   5,   5,  10,  10,   5,   5, -10,
+   -10,   0,  10,  10,  10,  10,   0, -10,
+   -10,  10,  10,  10,  10,  10,  10, -10,
+   -10,   5,   0,   0,   0,   0,   5, -10,
+   -20, -10, -10, -10, -10, -10, -10, -20,
+];
+#[rustfmt::skip]
+const ROOK: [i32; 64] = [
+     0,   0,   0,   0,   0,   0,   0,   0,
+     5,  10,  10,  10,  10,  10,  10,   5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+     0,   0,   0,   5,   5,   0,   0,   0,
+];
+#[rustfmt::skip]
+const QUEEN: [i32; 64] = [
+   -20, -10, -10,  -5,  -5, -10, -10, -20,
+   -10,   0,   0,   0,   0,   0,   0, -10,
+   -10,   0,   5,   5,   5,   5,   0, -10,
+    -5,   0,   5,   5,   5,   5,   0,  -5,
+     0,   0,   5,   5,   5,   5,   0,  -5,
+   -10,   5,   5,   5,   5,   5,   0, -10,
+   -10,   0,   5,   0,   0,   0,   0, -10,
+   -20, -10, -10,  -5,  -5, -10, -10, -20,
+];
+// Tucked away behind its pawns in the middlegame, centralized in the endgame
+#[rustfmt::skip]
+const KING_MG: [i32; 64] = [
+   -30, -40, -40, -50, -50, -40, -40, -30,
+   -30, -40, -40, -50, -50, -40, -40, -30,
+   -30, -40, -40, -50, -50, -40, -40, -30,
+   -30, -40, -40, -50, -50, -40, -40, -30,
+   -20, -30, -30, -40, -40, -30, -30, -20,
+   -10, -20, -20, -20, -20, -20, -20, -10,
+    20,  20,   0,   0,   0,   0,  20,  20,
+    20,  30,  10,   0,   0,  10,  30,  20,
+];
+#[rustfmt::skip]
+const KING_EG: [i32; 64] = [
+   -50, -40, -30, -20, -20, -30, -40, -50,
+   -30, -20, -10,   0,   0, -10, -20, -30,
+   -30, -10,  20,  30,  30,  20, -10, -30,
+   -30, -10,  30,  40,  40,  30, -10, -30,
+   -30, -10,  30,  40,  40,  30, -10, -30,
+   -30, -10,  20,  30,  30,  20, -10, -30,
+   -30, -30,   0,   0,   0,   0, -30, -30,
+   -50, -30, -30, -30, -30, -30, -30, -50,
+];
+
+const fn combine(mg: [i32; 64], eg: [i32; 64]) -> [Tapered; 64] {
+    let mut table = [Tapered::ZERO; 64];
+    let mut i = 0;
+    while i < 64 {
+        table[i] = Tapered::new(mg[i], eg[i]);
+        i += 1;
+    }
+    table
+}
+
+// Indexed by piece type
+const PIECE_SQUARES: [[Tapered; 64]; 6] = [
+    combine(PAWN_MG, PAWN_EG),
+    combine(KNIGHT, KNIGHT),
+    combine(BISHOP, BISHOP),
+    combine(ROOK, ROOK),
+    combine(QUEEN, QUEEN),
+    combine(KING_MG, KING_EG),
+];
+
+/// The evaluation split up into its terms, for debugging.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Breakdown {
+    /// Indexed like [`Term::ALL`], from White's point of view
+    pub terms: [Tapered; Term::COUNT],
+    pub phase: Phase,
+    pub side: Side,
+}
+
+impl Breakdown {
+    pub fn new(node: &Node) -> Self {
+        Self::with_pawns(node, pawn_structure(node))
+    }
+
+    // The pawn structure term is passed in, so that it can come from a [`PawnTable`]
+    fn with_pawns(node: &Node, pawns: Tapered) -> Self {
+        let mut terms = [Tapered::ZERO; Term::COUNT];
+        terms[Term::Pawns as usize] = pawns;
+        for side in [Side::White, Side::Black] {
+            let sign = match side {
+                Side::White => 1,
+                Side::Black => -1,
+            };
+            let mut material = Tapered::ZERO;
+            let mut piece_squares = Tapered::ZERO;
+            for piece_type in PIECE_TYPES {
+                let pieces = node.piece(Piece::from_side_piece(side, piece_type));
+                material += MATERIAL[piece_type.to_index() as usize] * pieces.popcount() as i32;
+                for (sq, _) in pieces {
+                    // The tables have a8 first, so White's squares need flipping, and Black's
+                    // mirrored ones don't
+                    let index = match side {
+                        Side::White => sq.to_index() ^ 56,
+                        Side::Black => sq.to_index(),
+                    };
+                    piece_squares += PIECE_SQUARES[piece_type.to_index() as usize][index as usize];
+                }
+            }
+            terms[Term::Material as usize] += material * sign;
+            terms[Term::PieceSquares as usize] += piece_squares * sign;
+
+            let bishops = node.piece(Piece::from_side_piece(side, PieceType::Bishop));
+            if bishops.popcount() >= 2 {
+                terms[Term::BishopPair as usize] += BISHOP_PAIR * sign;
+            }
+            terms[Term::Mobility as usize] += mobility(node, side) * sign;
+            terms[Term::KingSafety as usize] += king_safety(node, side) * sign;
+            if node.side == side {
+                terms[Term::Tempo as usize] += TEMPO * sign;
+            }
+        }
+        Breakdown {
+            terms,
+            phase: Phase::of(node),
+            side: node.side,
+        }
+    }
+
+    pub const fn term(&self, term: Term) -> Tapered {
+        self.terms[term as usize]
+    }
+
+    /// The sum of all terms, from White's point of view.
+    pub fn total(&self) -> Tapered {
+        self.terms
+            .iter()
+            .fold(Tapered::ZERO, |sum, &term| sum + term)
+    }
+
+    /// The blended total from the side to move's point of view, which is what [`evaluate`]
+    /// returns.
+    pub fn score(&self) -> i32 {
+        let score = self.total().blend(self.phase);
+        match self.side {
+            Side::White => score,
+            Side::Black => -score,
+        }
+    }
+}
+
+impl fmt::Display for Breakdown {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:<14}{:>7}{:>7}{:>7}", "Term", "MG", "EG", "Total")?;
+        let row = |f: &mut fmt::Formatter<'_>, name: &str, score: Tapered| {
+            writeln!(
+                f,
+                "{:<14}{:>7}{:>7}{:>7}",
+                name,
+                score.mg,
+                score.eg,
+                score.blend(self.phase)
+            )
+        };
+        for term in Term::ALL {
+            row(f, term.name(), self.term(term))?;
+        }
+        row(f, "Total", self.total())?;
+        writeln!(
+            f,
+            "Phase {}/{}, scores from White's point of view",
+            self.phase.0,
+            Phase::MAX
+        )?;
+        write!(f, "Final evaluation {} for the side to move", self.score())
+    }
+}
+
+/// Static evaluation of `node` in centipawns, from the side to move's point of view.
+pub fn evaluate(node: &Node) -> i32 {
+    Breakdown::new(node).score()
+}
+
+/// Evaluates like [`evaluate`], but caches the pawn structure term between calls.
+#[derive(Default)]
+pub struct Evaluator {
+    pawns: PawnTable,
+}
+
+impl Evaluator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn evaluate(&mut self, node: &Node) -> i32 {
+        Breakdown::with_pawns(node, self.pawns.get(node)).score()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        eval::{Breakdown, Evaluator, Phase, TEMPO, Tapered, Term, evaluate},
+        perft::node::Node,
+        position::Position,
+    };
+
+    fn node(fen: &str) -> Node {
+        Position::parse_fen(fen).unwrap().to_node()
+    }
+
+    // The same position with the colors swapped
+    fn mirror(fen: &str) -> String {
+        let fields: Vec<&str> = fen.split(' ').collect();
+        let swap_case = |s: &str| {
+            s.chars()
+                .map(|c| match c {
+                    c if c.is_ascii_uppercase() => c.to_ascii_lowercase(),
+                    c => c.to_ascii_uppercase(),
+                })
+                .collect::<String>()
+        };
+        let board: Vec<&str> = fields[0].split('/').rev().collect();
+        let side = if fields[1] == "w" { "b" } else { "w" };
+        let en_passant: String = fields[3]
+            .chars()
+            .map(|c| match c {
+                '3' => '6',
+                '6' => '3',
+                c => c,
+            })
+            .collect();
+        format!(
+            "{} {} {} {} {} {}",
+            swap_case(&board.join("/")),
+            side,
+            swap_case(fields[2]),
+            en_passant,
+            fields[4],
+            fields[5]
+        )
+    }
+
+    const FENS: [&str; 5] = [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1",
+        "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+    ];
+
+    #[test]
+    fn symmetry() {
+        for fen in FENS {
+            let mirrored = mirror(fen);
+            assert_eq!(evaluate(&node(fen)), evaluate(&node(&mirrored)), "{}", fen);
+        }
+        // Only the side to move differs in the start position
+        let start = Breakdown::new(&Node::POSITION_1);
+        assert_eq!(start.total(), TEMPO);
+        assert_eq!(start.score(), TEMPO.mg);
+    }
+
+    #[test]
+    fn breakdown() {
+        for fen in FENS {
+            let breakdown = Breakdown::new(&node(fen));
+            let sum = Term::ALL
+                .iter()
+                .fold(Tapered::ZERO, |sum, &term| sum + breakdown.term(term));
+            assert_eq!(sum, breakdown.total());
+            assert_eq!(breakdown.score(), evaluate(&node(fen)));
+        }
+
+        let mut evaluator = Evaluator::new();
+        for fen in FENS.iter().chain(FENS.iter()) {
+            assert_eq!(evaluator.evaluate(&node(fen)), evaluate(&node(fen)));
+        }
+
+        // White has the bishops, Black a knight instead
+        let breakdown = Breakdown::new(&node("4k3/8/8/8/8/8/8/2B1KB2 b - - 0 1"));
+        assert!(breakdown.term(Term::BishopPair).mg > 0);
+        assert!(breakdown.term(Term::Material).mg > 0);
+        assert_eq!(breakdown.term(Term::Tempo), -TEMPO);
+        assert!(breakdown.score() < 0);
+        assert!(breakdown.to_string().contains("Bishop pair"));
+    }
+
+    #[test]
+    fn phase() {
+        assert_eq!(Phase::of(&Node::POSITION_1), Phase(Phase::MAX));
+        assert_eq!(
+            Phase::of(&node("4k3/pppp4/8/8/8/8/PPPP4/4K3 w - - 0 1")),
+            Phase(0)
+        );
+        assert_eq!(
+            Phase::of(&node("3qk3/8/8/8/8/8/8/3QK3 w - - 0 1")),
+            Phase(8)
+        );
+        // Extra queens don't go past the maximum
+        assert_eq!(
+            Phase::of(&node("qqqqkqqq/8/8/8/8/8/8/QQQQKQQQ w - - 0 1")),
+            Phase(Phase::MAX)
+        );
+        let score = Tapered::new(100, 200);
+        assert_eq!(score.blend(Phase(Phase::MAX)), 100);
+        assert_eq!(score.blend(Phase(0)), 200);
+        assert_eq!(score.blend(Phase(Phase::MAX / 2)), 150);
+    }
+
+    #[test]
+    fn sensible_preferences() {
+        // Centralized knights
+        assert!(
+            evaluate(&node("4k3/8/8/8/3N4/8/8/4K3 w - - 0 1"))
+                > evaluate(&node("4k3/8/8/8/8/8/8/N3K3 w - - 0 1"))
+        );
+        // Advanced pawns in the endgame
+        assert!(
+            evaluate(&node("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1"))
+                > evaluate(&node("4k3/8/8/8/8/8/1P6/4K3 w - - 0 1"))
+        );
+        // A castled king in the middlegame
+        let castled = "r1bq1rk1/pppp1ppp/2n2n2/2b1p3/2B1P3/2N2N2/PPPP1PPP/R1BQ1RK1 w - - 0 1";
+        let exposed = "r1bq1rk1/pppp1ppp/2n2n2/2b1p3/2B1P3/2N1KN2/PPPP1PPP/R1BQ1R2 w - - 0 1";
+        assert!(evaluate(&node(castled)) > evaluate(&node(exposed)));
+        // Passed pawns over blocked ones
+        assert!(
+            evaluate(&node("4k3/7p/8/8/2P5/8/8/4K3 w - - 0 1"))
+                > evaluate(&node("4k3/2p5/8/8/2P5/8/8/4K3 w - - 0 1"))
+        );
+        // Healthy pawns over doubled isolated ones
+        assert!(
+            evaluate(&node("4k3/pp6/8/8/8/8/PP6/4K3 w - - 0 1"))
+                > evaluate(&node("4k3/pp6/8/8/8/P7/P7/4K3 w - - 0 1"))
+        );
+    }
+}
@@ -0,0 +1,257 @@
+use crate::{
+    bitboard::BitBoard,
+    perft::node::Node,
+    piece::{Piece, Side},
+};
+
+use super::Tapered;
+
+// Indexed by rank from the pawn's own point of view
+const PASSED: [Tapered; 8] = [
+    Tapered::new(0, 0),
+    Tapered::new(5, 10),
+    Tapered::new(10, 15),
+    Tapered::new(15, 25),
+    Tapered::new(25, 45),
+    Tapered::new(40, 75),
+    Tapered::new(60, 120),
+    Tapered::new(0, 0),
+];
+const CANDIDATE: [Tapered; 8] = [
+    Tapered::new(0, 0),
+    Tapered::new(2, 4),
+    Tapered::new(4, 6),
+    Tapered::new(6, 10),
+    Tapered::new(10, 18),
+    Tapered::new(15, 30),
+    Tapered::new(0, 0),
+    Tapered::new(0, 0),
+];
+const CONNECTED: [Tapered; 8] = [
+    Tapered::new(0, 0),
+    Tapered::new(3, 2),
+    Tapered::new(5, 4),
+    Tapered::new(8, 6),
+    Tapered::new(12, 10),
+    Tapered::new(20, 15),
+    Tapered::new(30, 25),
+    Tapered::new(0, 0),
+];
+const ISOLATED: Tapered = Tapered::new(-10, -15);
+const DOUBLED: Tapered = Tapered::new(-10, -20);
+const BACKWARD: Tapered = Tapered::new(-8, -10);
+
+/// One side's pawns, sorted into the features the pawn structure term scores. A pawn can have
+/// several features, like being both passed and connected.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct PawnFeatures {
+    /// No enemy pawns in front of it or on the adjacent files ahead, nor a pawn of its own
+    pub passed: BitBoard,
+    /// Not passed yet, but on a file without enemy pawns ahead, with at least as many pawns to
+    /// help it advance as there are enemy pawns that can stop it
+    pub candidates: BitBoard,
+    /// Defended by a pawn, or next to one
+    pub connected: BitBoard,
+    /// No pawns of its own on the adjacent files
+    pub isolated: BitBoard,
+    /// Another pawn of its own in front of it
+    pub doubled: BitBoard,
+    /// Behind the pawns on the adjacent files, and can't advance without being taken
+    pub backward: BitBoard,
+}
+
+impl PawnFeatures {
+    pub fn new(node: &Node, side: Side) -> Self {
+        Self::with_score(node, side).0
+    }
+
+    // Black's pawns are flipped onto White's side of the board, so everything only has to be
+    // written down for pawns that move north
+    fn with_score(node: &Node, side: Side) -> (Self, Tapered) {
+        let white = node.piece(Piece::WhitePawn);
+        let black = node.piece(Piece::BlackPawn);
+        match side {
+            Side::White => Self::white_view(white, black),
+            Side::Black => {
+                let (features, score) = Self::white_view(black.vflip(), white.vflip());
+                (features.vflip(), score)
+            }
+        }
+    }
+
+    fn white_view(own: BitBoard, enemy: BitBoard) -> (Self, Tapered) {
+        let own_attacks = own.sideways().lshift(8);
+        let enemy_attacks = enemy.sideways().rshift(8);
+        let mut features = PawnFeatures::default();
+        let mut score = Tapered::ZERO;
+        for (sq, pawn) in own {
+            let rank = (sq.to_index() / 8) as usize;
+            let front = pawn.north_span();
+            let neighbours = own.intersect(pawn.adjacent_files());
+            // Pawns on the adjacent files that could come to its support
+            let helpers = own.intersect(pawn.south_fill().sideways());
+            // Enemy pawns on the adjacent files that can take it on the way
+            let sentries = enemy.intersect(front.sideways());
+
+            if !enemy.intersects(front) && !own.intersects(front) {
+                if sentries.is_empty() {
+                    features.passed.set_assign(sq);
+                    score += PASSED[rank];
+                } else if helpers.popcount() >= sentries.popcount() {
+                    features.candidates.set_assign(sq);
+                    score += CANDIDATE[rank];
+                }
+            }
+            if own_attacks.intersects(pawn) || own.intersects(pawn.sideways()) {
+                features.connected.set_assign(sq);
+                score += CONNECTED[rank];
+            }
+            if neighbours.is_empty() {
+                features.isolated.set_assign(sq);
+                score += ISOLATED;
+            } else if helpers.is_empty() && enemy_attacks.intersects(pawn.north()) {
+                features.backward.set_assign(sq);
+                score += BACKWARD;
+            }
+            if own.intersects(front) {
+                features.doubled.set_assign(sq);
+                score += DOUBLED;
+            }
+        }
+        (features, score)
+    }
+
+    const fn vflip(self) -> Self {
+        PawnFeatures {
+            passed: self.passed.vflip(),
+            candidates: self.candidates.vflip(),
+            connected: self.connected.vflip(),
+            isolated: self.isolated.vflip(),
+            doubled: self.doubled.vflip(),
+            backward: self.backward.vflip(),
+        }
+    }
+}
+
+/// The pawn structure term from White's point of view. It only depends on the pawns, so
+/// [`PawnTable`] can cache it by [`Node::pawn_hash`].
+pub fn pawn_structure(node: &Node) -> Tapered {
+    PawnFeatures::with_score(node, Side::White).1 - PawnFeatures::with_score(node, Side::Black).1
+}
+
+#[derive(Clone, Copy, Default)]
+struct PawnEntry {
+    key: u64,
+    score: Tapered,
+}
+
+/// Direct-mapped cache of [`pawn_structure`]. The pawn structure rarely changes between nodes of
+/// the search, so most lookups hit.
+pub struct PawnTable {
+    entries: Box<[PawnEntry]>,
+}
+
+impl PawnTable {
+    pub const DEFAULT_ENTRIES: usize = 1 << 14;
+
+    /// `entries` is rounded up to a power of two.
+    pub fn new(entries: usize) -> Self {
+        // An empty entry has key 0 and score 0, which is also what a board without pawns hashes
+        // and scores to, so it doesn't need to be told apart from a real one
+        PawnTable {
+            entries: vec![PawnEntry::default(); entries.next_power_of_two()].into_boxed_slice(),
+        }
+    }
+
+    pub fn get(&mut self, node: &Node) -> Tapered {
+        let index = node.pawn_hash as usize & (self.entries.len() - 1);
+        let entry = &mut self.entries[index];
+        if entry.key != node.pawn_hash {
+            *entry = PawnEntry {
+                key: node.pawn_hash,
+                score: pawn_structure(node),
+            };
+        }
+        entry.score
+    }
+}
+
+impl Default for PawnTable {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_ENTRIES)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        bitboard::BitBoard,
+        coord::Square,
+        eval::pawns::{PawnFeatures, PawnTable, pawn_structure},
+        perft::node::Node,
+        piece::Side,
+        position::Position,
+    };
+
+    fn features(fen: &str, side: Side) -> PawnFeatures {
+        PawnFeatures::new(&Position::parse_fen(fen).unwrap().to_node(), side)
+    }
+
+    fn squares(squares: &[Square]) -> BitBoard {
+        BitBoard::from_squares(squares.iter().copied())
+    }
+
+    #[test]
+    fn classification() {
+        let fen = "4k3/5p2/4p1p1/3P1p1p/8/P1P5/P7/4K3 w - - 0 1";
+        let white = features(fen, Side::White);
+        assert_eq!(white.passed, squares(&[Square::A3, Square::C3]));
+        // e6 can stop d5, but c3 can come to its help
+        assert_eq!(white.candidates, squares(&[Square::D5]));
+        assert_eq!(white.isolated, squares(&[Square::A2, Square::A3]));
+        assert_eq!(white.doubled, squares(&[Square::A2]));
+        assert_eq!(white.connected, BitBoard::EMPTY);
+
+        let black = features(fen, Side::Black);
+        assert_eq!(black.passed, squares(&[Square::F5, Square::G6, Square::H5]));
+        assert_eq!(black.candidates, squares(&[Square::E6]));
+        assert_eq!(
+            black.connected,
+            squares(&[Square::E6, Square::F5, Square::G6, Square::H5])
+        );
+        assert_eq!(black.doubled, squares(&[Square::F7]));
+        assert_eq!(black.isolated, BitBoard::EMPTY);
+        assert_eq!(black.backward, BitBoard::EMPTY);
+
+        // b3 is defended by c2, which will help it past c5
+        let white = features("4k3/8/8/2p5/8/1P6/2P5/4K3 w - - 0 1", Side::White);
+        assert_eq!(white.connected, squares(&[Square::B3]));
+        assert_eq!(white.candidates, squares(&[Square::B3]));
+
+        // d2 defends c3 and e3, but was left behind by them and e4 covers d3
+        let white = features("4k3/8/8/8/4p3/2P1P3/3P4/4K3 w - - 0 1", Side::White);
+        assert_eq!(white.backward, squares(&[Square::D2]));
+        assert_eq!(white.connected, squares(&[Square::C3, Square::E3]));
+    }
+
+    #[test]
+    fn symmetric() {
+        let node = Position::parse_fen("4k3/p4p2/1p2p1p1/3P1p1p/8/P1P5/P7/4K3 w - - 0 1")
+            .unwrap()
+            .to_node();
+        let mirrored = Position::parse_fen("4k3/p7/p1p5/8/3p1P1P/1P2P1P1/P4P2/4K3 b - - 0 1")
+            .unwrap()
+            .to_node();
+        assert_eq!(pawn_structure(&node), -pawn_structure(&mirrored));
+        assert_eq!(pawn_structure(&Node::POSITION_1), Default::default());
+    }
+
+    #[test]
+    fn table() {
+        let mut table = PawnTable::new(100);
+        for node in [Node::POSITION_1, Node::POSITION_2, Node::POSITION_3] {
+            assert_eq!(table.get(&node), pawn_structure(&node));
+            assert_eq!(table.get(&node), pawn_structure(&node));
+        }
+    }
+}
@@ -1,12 +1,20 @@
 #[allow(long_running_const_eval)]
 pub mod bitboard;
 pub mod bitboard_iter;
+pub mod book;
 pub mod castling_rights;
 pub mod coord;
+pub mod eval;
 pub mod moves;
 pub mod perft;
+pub mod pgn;
 pub mod piece;
 pub mod pieces;
+pub mod polyglot;
 pub mod position;
 pub mod print_board;
+pub mod san;
+pub mod search;
+pub mod tt;
+pub mod uci;
 pub mod zobrist_table;
@@ -1,13 +1,85 @@
+use std::fmt;
+
+use crate::{coord::Square, piece::PieceType};
+
+// Layout, from least to most significant:
+//   bits  0-5: source square
+//   bits 6-11: destination square
+//   bits 12-15: MoveType
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Move {
     bits: u16,
 }
 
 impl Move {
-    // pub fn new(from: Square) -> Self {}
+    /// Placeholder for "no move", e.g. in hash table entries or UCI's `0000`.
+    /// It can never be confused with a real move, since its source and destination are the same.
+    pub const NULL: Move = Move { bits: 0 };
+    pub const fn new(from: Square, to: Square, kind: MoveType) -> Self {
+        Move {
+            bits: from.to_index() as u16 | (to.to_index() as u16) << 6 | (kind as u16) << 12,
+        }
+    }
+    pub const fn from(self) -> Square {
+        Square::from_index((self.bits & 0x3F) as u8).unwrap()
+    }
+    pub const fn to(self) -> Square {
+        Square::from_index((self.bits >> 6 & 0x3F) as u8).unwrap()
+    }
+    pub const fn kind(self) -> MoveType {
+        MoveType::from_index((self.bits >> 12) as u8).unwrap()
+    }
+    pub const fn is_null(self) -> bool {
+        self.bits == Self::NULL.bits
+    }
+    pub const fn promotion(self) -> Option<PieceType> {
+        self.kind().promotion()
+    }
+    pub fn is_capture(self) -> bool {
+        self.kind().is_capture()
+    }
+    pub const fn is_castle(self) -> bool {
+        self.kind().is_castle()
+    }
+    pub const fn to_bits(self) -> u16 {
+        self.bits
+    }
+    pub const fn from_bits(bits: u16) -> Option<Self> {
+        match MoveType::from_index((bits >> 12) as u8) {
+            Some(_) => Some(Move { bits }),
+            None => None,
+        }
+    }
+}
+
+// Long algebraic notation, as used by UCI
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_null() {
+            return write!(f, "0000");
+        }
+        write!(f, "{}{}", self.from(), self.to())?;
+        match self.promotion() {
+            Some(PieceType::Knight) => write!(f, "n"),
+            Some(PieceType::Bishop) => write!(f, "b"),
+            Some(PieceType::Rook) => write!(f, "r"),
+            Some(PieceType::Queen) => write!(f, "q"),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl fmt::Debug for Move {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_null() {
+            return write!(f, "Move::NULL");
+        }
+        write!(f, "{}{} ({:?})", self.from(), self.to(), self.kind())
+    }
 }
 
 #[rustfmt::skip]
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum MoveType {
     Quiet                ,
     DoublePush           ,
@@ -27,16 +99,154 @@ pub enum MoveType {
 }
 
 impl MoveType {
+    pub const fn from_index(index: u8) -> Option<Self> {
+        match index {
+            0 => Some(MoveType::Quiet),
+            1 => Some(MoveType::DoublePush),
+            2 => Some(MoveType::CastleKingside),
+            3 => Some(MoveType::CastleQueenside),
+            4 => Some(MoveType::PromoteKnight),
+            5 => Some(MoveType::PromoteBishop),
+            6 => Some(MoveType::PromoteRook),
+            7 => Some(MoveType::PromoteQueen),
+            8 => Some(MoveType::Capture),
+            9 => Some(MoveType::CaptureEnPassant),
+            10 => Some(MoveType::PromoteCaptureKnight),
+            11 => Some(MoveType::PromoteCaptureBishop),
+            12 => Some(MoveType::PromoteCaptureRook),
+            13 => Some(MoveType::PromoteCaptureQueen),
+            _ => None,
+        }
+    }
+
     #[inline(never)]
     pub fn is_capture(self) -> bool {
+        matches!(
+            self,
+            MoveType::Capture
+                | MoveType::CaptureEnPassant
+                | MoveType::PromoteCaptureKnight
+                | MoveType::PromoteCaptureBishop
+                | MoveType::PromoteCaptureRook
+                | MoveType::PromoteCaptureQueen
+        )
+    }
+
+    pub const fn is_castle(self) -> bool {
+        matches!(self, MoveType::CastleKingside | MoveType::CastleQueenside)
+    }
+
+    pub const fn promotion(self) -> Option<PieceType> {
         match self {
-            MoveType::Capture => true,
-            MoveType::CaptureEnPassant => true,
-            MoveType::PromoteCaptureKnight => true,
-            MoveType::PromoteCaptureBishop => true,
-            MoveType::PromoteCaptureRook => true,
-            MoveType::PromoteCaptureQueen => true,
-            _ => false,
+            MoveType::PromoteKnight | MoveType::PromoteCaptureKnight => Some(PieceType::Knight),
+            MoveType::PromoteBishop | MoveType::PromoteCaptureBishop => Some(PieceType::Bishop),
+            MoveType::PromoteRook | MoveType::PromoteCaptureRook => Some(PieceType::Rook),
+            MoveType::PromoteQueen | MoveType::PromoteCaptureQueen => Some(PieceType::Queen),
+            _ => None,
         }
     }
 }
+
+// 218 is the most legal moves any known position has, so this leaves plenty of room.
+pub const MAX_MOVES: usize = 256;
+
+/// Fixed-capacity move buffer, so move generation never has to allocate.
+#[derive(Clone)]
+pub struct MoveList {
+    moves: [Move; MAX_MOVES],
+    len: usize,
+}
+
+impl MoveList {
+    pub const fn new() -> Self {
+        MoveList {
+            moves: [Move::NULL; MAX_MOVES],
+            len: 0,
+        }
+    }
+    #[inline]
+    pub fn push(&mut self, mv: Move) {
+        self.moves[self.len] = mv;
+        self.len += 1;
+    }
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+    pub fn as_slice(&self) -> &[Move] {
+        &self.moves[..self.len]
+    }
+    pub fn as_mut_slice(&mut self) -> &mut [Move] {
+        &mut self.moves[..self.len]
+    }
+    pub fn iter(&self) -> std::slice::Iter<'_, Move> {
+        self.as_slice().iter()
+    }
+    pub fn contains(&self, mv: Move) -> bool {
+        self.as_slice().contains(&mv)
+    }
+}
+
+impl Default for MoveList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> IntoIterator for &'a MoveList {
+    type Item = &'a Move;
+    type IntoIter = std::slice::Iter<'a, Move>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quickcheck_macros::quickcheck;
+
+    use crate::{
+        coord::Square,
+        moves::{Move, MoveType},
+        piece::PieceType,
+    };
+
+    #[quickcheck]
+    fn move_roundtrip(from: Square, to: Square, kind: u8) -> bool {
+        let kind = MoveType::from_index(kind % 14).unwrap();
+        let mv = Move::new(from, to, kind);
+        mv.from() == from && mv.to() == to && mv.kind() == kind
+    }
+
+    #[quickcheck]
+    fn bits_roundtrip(from: Square, to: Square, kind: u8) -> bool {
+        let kind = MoveType::from_index(kind % 14).unwrap();
+        let mv = Move::new(from, to, kind);
+        Move::from_bits(mv.to_bits()) == Some(mv)
+    }
+
+    #[test]
+    fn unit() {
+        assert_eq!(std::mem::size_of::<Move>(), 2);
+        assert_eq!(Move::from_bits(0xF000), None);
+
+        let mv = Move::new(Square::E7, Square::F8, MoveType::PromoteCaptureKnight);
+        assert_eq!(mv.to_string(), "e7f8n");
+        assert_eq!(mv.promotion(), Some(PieceType::Knight));
+        assert!(mv.is_capture());
+        assert!(!mv.is_castle());
+        assert_eq!(format!("{:?}", mv), "e7f8 (PromoteCaptureKnight)");
+
+        let mv = Move::new(Square::E1, Square::G1, MoveType::CastleKingside);
+        assert_eq!(mv.to_string(), "e1g1");
+        assert!(mv.is_castle());
+        assert!(!mv.is_capture());
+        assert_eq!(mv.promotion(), None);
+
+        assert!(Move::NULL.is_null());
+        assert!(!mv.is_null());
+        assert_eq!(Move::NULL.to_string(), "0000");
+    }
+}
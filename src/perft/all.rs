@@ -1,8 +1,13 @@
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+};
+
 use crate::{
     bitboard::BitBoard,
-    castling_rights::CastlingRights,
     coord::Square,
-    piece::{Piece, PieceType, Side},
+    moves::Move,
+    piece::{Piece, Side},
     pieces::{
         bishop::bishop_moves,
         king::king_moves,
@@ -11,21 +16,23 @@ use crate::{
         queen::queen_moves,
         rook::rook_moves,
     },
-    position::Position,
-    zobrist_table::ZOBRIST_TABLE,
 };
 
+use super::node::Node;
+
 impl Node {
-    fn count_jumper_moves<F: Fn(Square) -> BitBoard>(&self, piece: Piece, movegen: F) -> usize {
+    /// The number of squares in `targets` the pieces of type `piece` can move to, summed over
+    /// all of them. Pins and checks are ignored.
+    pub fn count_jumper_moves<F: Fn(Square) -> BitBoard>(
+        &self,
+        piece: Piece,
+        targets: BitBoard,
+        movegen: F,
+    ) -> usize {
         assert!(piece.piece_type().is_jumper()); // This _should_ always be optimized out, and
-                                                 // provides an easy check if everything is inlined correctly
+        // provides an easy check if everything is inlined correctly
         self.piece(piece)
-            .iter()
-            .map(|(sq, _)| {
-                movegen(sq)
-                    .difference(self.occupancy(piece.side()))
-                    .popcount() as usize
-            })
+            .map(|(sq, _)| movegen(sq).intersect(targets).popcount() as usize)
             .sum()
     }
 
@@ -42,98 +49,159 @@ impl Node {
         }
     }
 
-    fn count_slider_moves<F: Fn(Square, BitBoard) -> BitBoard>(
+    /// Like [`Node::count_jumper_moves`], for sliders blocked by the pieces on the board.
+    pub fn count_slider_moves<F: Fn(Square, BitBoard) -> BitBoard>(
         &self,
         piece: Piece,
+        targets: BitBoard,
         movegen: F,
     ) -> usize {
         assert!(piece.piece_type().is_slider()); // This _should_ always be optimized out and
-                                                 // provides an easy check if everything is inlined correctly
+        // provides an easy check if everything is inlined correctly
         self.piece(piece)
-            .iter()
             .map(|(sq, _)| {
                 movegen(sq, self.occupancy_total)
-                    .difference(self.occupancy(piece.side()))
+                    .intersect(targets)
                     .popcount() as usize
             })
             .sum()
     }
 
-    pub fn perft(&self, depth: u8) -> usize {
-        self.perft_white(depth)
-    }
-
-    pub fn perft_white(&self, depth: u8) -> usize {
-        if depth > 0 {
-            // pawn push single promote
-            // pawn push single
-            // pawn push double
-            // pawn attack
-            // pawn attack promote
-            // knight
-            // bishop
-            // rook
-            // queen
-            // king
-            // castle
-            todo!()
-        } else {
-            if self.white_king_attacked() {
-                0
-            } else {
-                1
+    /// The number of leaf nodes of the legal move tree `depth` plies deep.
+    pub fn perft(&self, depth: u8) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        let moves = self.legal_moves();
+        match depth {
+            1 => moves.len() as u64,
+            _ => {
+                let mut node = self.clone();
+                moves
+                    .iter()
+                    .map(|&mv| {
+                        let undo = node.make(mv);
+                        let leaves = node.perft(depth - 1);
+                        node.unmake(mv, undo);
+                        leaves
+                    })
+                    .sum()
             }
         }
     }
 
-    pub fn perft_black(&self, depth: u8) -> usize {
-        if depth > 0 {
-            // pawn
-            // knight
-            // bishop
-            // rook
-            // queen
-            // king
-            // castle
-            todo!()
-        } else {
-            if self.black_king_attacked() {
-                0
-            } else {
-                1
+    /// Like [`Node::perft`], with the work spread over `threads` threads. The root moves alone
+    /// make for too few jobs of too different sizes, so deeper trees are split after two plies.
+    pub fn perft_parallel(&self, depth: u8, threads: usize) -> u64 {
+        self.split_perft(depth, threads, Node::perft)
+    }
+
+    // Counts the subtrees with `count`, spread over `threads` threads
+    pub(super) fn split_perft<F: Fn(&Node, u8) -> u64 + Sync>(
+        &self,
+        depth: u8,
+        threads: usize,
+        count: F,
+    ) -> u64 {
+        if depth < 2 || threads <= 1 {
+            return count(self, depth);
+        }
+        let split = if depth >= 3 { 2 } else { 1 };
+        let mut jobs = Vec::new();
+        self.collect_subtrees(split, &mut jobs);
+        let remaining = depth - split;
+
+        let next = AtomicUsize::new(0);
+        thread::scope(|scope| {
+            let workers: Vec<_> = (0..threads.min(jobs.len()))
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut leaves = 0;
+                        while let Some(node) = jobs.get(next.fetch_add(1, Ordering::Relaxed)) {
+                            leaves += count(node, remaining);
+                        }
+                        leaves
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .map(|worker| worker.join().unwrap())
+                .sum()
+        })
+    }
+
+    // The nodes `plies` plies below this one
+    fn collect_subtrees(&self, plies: u8, nodes: &mut Vec<Node>) {
+        if plies == 0 {
+            nodes.push(self.clone());
+            return;
+        }
+        for &mv in &self.legal_moves() {
+            self.make_move(mv).collect_subtrees(plies - 1, nodes);
+        }
+    }
+
+    /// [`Node::perft`] split up by root move, in the order the moves are generated. Comparing
+    /// this against another engine points out the subtree where move generation goes wrong.
+    pub fn perft_divide(&self, depth: u8) -> Vec<(Move, u64)> {
+        if depth == 0 {
+            return Vec::new();
+        }
+        let mut node = self.clone();
+        self.legal_moves()
+            .iter()
+            .map(|&mv| {
+                let undo = node.make(mv);
+                let leaves = node.perft(depth - 1);
+                node.unmake(mv, undo);
+                (mv, leaves)
+            })
+            .collect()
+    }
+
+    fn pawn_attacks(&self, side: Side) -> PawnAttacks {
+        match side {
+            Side::White => {
+                PawnAttacks::white(self.piece(Piece::WhitePawn), self.occupancy(Side::Black))
+            }
+            Side::Black => {
+                PawnAttacks::black(self.piece(Piece::BlackPawn), self.occupancy(Side::White))
             }
         }
     }
 
     pub fn count_black_moves(&self) -> usize {
+        let targets = self.occupancy(Side::Black).complement();
         self.pawn_pushes(Side::Black).count_moves() as usize
             + self.pawn_attacks(Side::Black).count_moves() as usize
-            + self.count_jumper_moves(Piece::BlackKnight, knight_moves)
-            + self.count_jumper_moves(Piece::BlackKing, king_moves)
-            + self.count_slider_moves(Piece::BlackBishop, bishop_moves)
-            + self.count_slider_moves(Piece::BlackRook, rook_moves)
-            + self.count_slider_moves(Piece::BlackQueen, queen_moves)
+            + self.count_jumper_moves(Piece::BlackKnight, targets, knight_moves)
+            + self.count_jumper_moves(Piece::BlackKing, targets, king_moves)
+            + self.count_slider_moves(Piece::BlackBishop, targets, bishop_moves)
+            + self.count_slider_moves(Piece::BlackRook, targets, rook_moves)
+            + self.count_slider_moves(Piece::BlackQueen, targets, queen_moves)
     }
 
     #[inline(never)]
     pub fn count_white_moves(&self) -> usize {
+        let targets = self.occupancy(Side::White).complement();
         self.pawn_pushes(Side::White).count_moves() as usize
             + self.pawn_attacks(Side::White).count_moves() as usize
-            + self.count_jumper_moves(Piece::WhiteKnight, knight_moves)
-            + self.count_jumper_moves(Piece::WhiteKing, king_moves)
-            + self.count_slider_moves(Piece::WhiteBishop, bishop_moves)
-            + self.count_slider_moves(Piece::WhiteRook, rook_moves)
-            + self.count_slider_moves(Piece::WhiteQueen, queen_moves)
+            + self.count_jumper_moves(Piece::WhiteKnight, targets, knight_moves)
+            + self.count_jumper_moves(Piece::WhiteKing, targets, king_moves)
+            + self.count_slider_moves(Piece::WhiteBishop, targets, bishop_moves)
+            + self.count_slider_moves(Piece::WhiteRook, targets, rook_moves)
+            + self.count_slider_moves(Piece::WhiteQueen, targets, queen_moves)
     }
 }
 
 #[cfg(test)]
 pub mod tests {
-    use super::*;
+    use crate::perft::node::Node;
 
     #[test]
     fn position_1() {
-        let n0 = Position::POSITION_1.to_bitboard();
+        let n0 = Node::POSITION_1;
         assert_eq!(n0.perft(0), 1);
         assert_eq!(n0.perft(1), 20);
         //               400
@@ -146,42 +214,74 @@ pub mod tests {
         // 2_439_530_234_167
     }
 
+    #[test]
+    fn divide() {
+        let node = Node::POSITION_2;
+        let divide = node.perft_divide(3);
+        assert_eq!(divide.len(), 48);
+        assert_eq!(divide.iter().map(|&(_, n)| n).sum::<u64>(), 97_862);
+        let castle = divide.iter().find(|(mv, _)| mv.to_string() == "e1g1");
+        assert_eq!(castle.map(|&(_, n)| n), Some(2_059));
+        assert!(node.perft_divide(0).is_empty());
+    }
+
+    #[test]
+    fn parallel() {
+        let positions = [
+            (Node::POSITION_1, 3),
+            (Node::POSITION_2, 3),
+            (Node::POSITION_3, 3),
+            (Node::POSITION_4, 3),
+            (Node::POSITION_5, 3),
+            (Node::POSITION_6, 3),
+        ];
+        for (node, depth) in positions {
+            let expected = node.perft(depth);
+            for threads in [1, 2, 3, 8] {
+                for depth in [0, 1, 2, depth] {
+                    assert_eq!(node.perft_parallel(depth, threads), node.perft(depth));
+                }
+                assert_eq!(node.perft_parallel(depth, threads), expected);
+            }
+        }
+    }
+
     #[test]
     fn position_2() {
-        let n0 = Position::POSITION_2.to_bitboard();
+        let n0 = Node::POSITION_2;
         // Castling
-        assert_eq!(n0.count_white_moves(), 48);
+        assert_eq!(n0.perft(1), 48);
         // 2039 97862 4085603 193690690 8031647685
     }
 
     #[test]
     fn position_3() {
-        let n0 = Position::POSITION_3.to_bitboard();
+        let n0 = Node::POSITION_3;
         // Discovered checks
-        assert_eq!(n0.count_white_moves(), 14);
+        assert_eq!(n0.perft(1), 14);
         // 191 2812 43238 674624 11030083 178633661 3009794393
     }
 
     #[test]
     fn position_4() {
-        let n0 = Position::POSITION_4.to_bitboard();
+        let n0 = Node::POSITION_4;
         // King in check
-        assert_eq!(n0.count_white_moves(), 6);
+        assert_eq!(n0.perft(1), 6);
         // 264 9467 422333 15833292 706045033
     }
 
     #[test]
     fn position_5() {
-        let n0 = Position::POSITION_5.to_bitboard();
+        let n0 = Node::POSITION_5;
         // Castling
-        assert_eq!(n0.count_white_moves(), 44);
+        assert_eq!(n0.perft(1), 44);
         // 1486 62379 2103487 89941194
     }
 
     #[test]
     fn position_6() {
-        let n0 = Position::POSITION_6.to_bitboard();
-        assert_eq!(n0.count_white_moves(), 46);
+        let n0 = Node::POSITION_6;
+        assert_eq!(n0.perft(1), 46);
         // 2079 89,890 3,894,594 164,075,551 6,923,051,137 287,188,994,746 11,923,589,843,526
         //      490,154,852,788,714
     }
@@ -0,0 +1,116 @@
+use std::mem;
+
+use crate::tt::{Slot, hash_index};
+
+use super::node::Node;
+
+/// Fixed-size cache of perft subtree counts, indexed by [`Node::hash`] and depth. Probing and
+/// storing don't lock, so all threads of [`Node::perft_parallel_hashed`] can use the same cache.
+pub struct PerftCache {
+    slots: Box<[Slot]>,
+}
+
+impl PerftCache {
+    // Layout of the data word: leaves (56) | depth (8)
+    const LEAF_BITS: u32 = 56;
+
+    pub fn new(size_mb: usize) -> Self {
+        let num_slots = (size_mb.max(1) << 20) / mem::size_of::<Slot>();
+        PerftCache {
+            slots: (0..num_slots).map(|_| Slot::default()).collect(),
+        }
+    }
+
+    fn slot(&self, hash: u64) -> &Slot {
+        &self.slots[hash_index(hash, self.slots.len())]
+    }
+
+    pub fn probe(&self, hash: u64, depth: u8) -> Option<u64> {
+        let (key, data) = self.slot(hash).load();
+        // An empty slot has depth 0, which is never stored
+        (depth > 0 && key == hash && (data >> Self::LEAF_BITS) as u8 == depth)
+            .then_some(data & ((1 << Self::LEAF_BITS) - 1))
+    }
+
+    /// Always replaces whatever was in the slot before.
+    pub fn store(&self, hash: u64, depth: u8, leaves: u64) {
+        debug_assert!(depth > 0 && leaves < 1 << Self::LEAF_BITS);
+        self.slot(hash)
+            .store(hash, leaves | (depth as u64) << Self::LEAF_BITS);
+    }
+}
+
+impl Node {
+    /// Like [`Node::perft`], but looks up and stores the counts of subtrees in `cache`, so that
+    /// transpositions are only counted once.
+    pub fn perft_hashed(&self, depth: u8, cache: &PerftCache) -> u64 {
+        // Counting the moves is cheaper than a cache lookup
+        if depth <= 1 {
+            return self.perft(depth);
+        }
+        if let Some(leaves) = cache.probe(self.hash, depth) {
+            return leaves;
+        }
+        let mut node = self.clone();
+        let leaves = self
+            .legal_moves()
+            .iter()
+            .map(|&mv| {
+                let undo = node.make(mv);
+                let leaves = node.perft_hashed(depth - 1, cache);
+                node.unmake(mv, undo);
+                leaves
+            })
+            .sum();
+        cache.store(self.hash, depth, leaves);
+        leaves
+    }
+
+    /// [`Node::perft_parallel`] and [`Node::perft_hashed`] combined, the threads share `cache`.
+    pub fn perft_parallel_hashed(&self, depth: u8, threads: usize, cache: &PerftCache) -> u64 {
+        self.split_perft(depth, threads, |node, depth| {
+            node.perft_hashed(depth, cache)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::perft::{cache::PerftCache, node::Node};
+
+    const POSITIONS: [(Node, u8); 6] = [
+        (Node::POSITION_1, 4),
+        (Node::POSITION_2, 3),
+        (Node::POSITION_3, 4),
+        (Node::POSITION_4, 3),
+        (Node::POSITION_5, 3),
+        (Node::POSITION_6, 3),
+    ];
+
+    #[test]
+    fn probe_store() {
+        let cache = PerftCache::new(1);
+        assert_eq!(cache.probe(0, 0), None);
+        assert_eq!(cache.probe(1234, 3), None);
+        cache.store(1234, 3, 97_862);
+        assert_eq!(cache.probe(1234, 3), Some(97_862));
+        assert_eq!(cache.probe(1234, 4), None);
+        assert_eq!(cache.probe(1235, 3), None);
+        cache.store(1234, 4, (1 << 56) - 1);
+        assert_eq!(cache.probe(1234, 4), Some((1 << 56) - 1));
+    }
+
+    #[test]
+    fn matches_perft() {
+        for (node, depth) in POSITIONS {
+            let expected = node.perft(depth);
+            // The small cache is full of collisions, the results must not change
+            let cache = PerftCache::new(1);
+            assert_eq!(node.perft_hashed(depth, &cache), expected);
+            // A second run is served from the cache
+            assert_eq!(node.perft_hashed(depth, &cache), expected);
+            let cache = PerftCache::new(1);
+            assert_eq!(node.perft_parallel_hashed(depth, 3, &cache), expected);
+        }
+    }
+}
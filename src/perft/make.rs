@@ -0,0 +1,286 @@
+use crate::{
+    castling_rights::CastlingRights,
+    moves::{Move, MoveType},
+    piece::{Piece, PieceType, Side},
+};
+
+use super::node::{EnPassantSquare, Node};
+
+/// Everything [`Node::unmake`] needs to restore that can't be derived from the move itself.
+#[derive(Clone, Copy)]
+pub struct Undo {
+    captured: Option<Piece>,
+    castling_rights: CastlingRights,
+    en_passant_square: EnPassantSquare,
+    halfmove_clock: u8,
+    hash: u64,
+    pawn_hash: u64,
+}
+
+impl Node {
+    /// Copy-make: returns the node after playing `mv`, which has to be legal.
+    pub fn make_move(&self, mv: Move) -> Node {
+        let mut node = self.clone();
+        node.make(mv);
+        node
+    }
+
+    /// Plays `mv` in place, which has to be legal. The returned [`Undo`] can be passed to
+    /// [`Node::unmake`] to restore the current node.
+    pub fn make(&mut self, mv: Move) -> Undo {
+        let side = self.side;
+        let captured = match mv.kind() {
+            MoveType::CaptureEnPassant => {
+                Some(Piece::from_side_piece(side.opponent(), PieceType::Pawn))
+            }
+            kind if kind.is_capture() => self.find_piece(side.opponent(), mv.to().to_bitboard()),
+            _ => None,
+        };
+        debug_assert_eq!(captured.is_some(), mv.is_capture());
+        let undo = Undo {
+            captured,
+            castling_rights: self.castling_rights,
+            en_passant_square: self.en_passant_square,
+            halfmove_clock: self.halfmove_clock,
+            hash: self.hash,
+            pawn_hash: self.pawn_hash,
+        };
+
+        let piece = self.move_pieces(mv);
+
+        if piece.piece_type() == PieceType::Pawn || captured.is_some() {
+            self.reset_halfmove_clock();
+        } else {
+            self.halfmove_clock = self.halfmove_clock.saturating_add(1);
+        }
+        if side == Side::Black {
+            self.move_clock += 1;
+        }
+
+        self.reset_en_passant();
+        if mv.kind() == MoveType::DoublePush {
+            let (from, to) = (mv.from().to_bitboard(), mv.to().to_bitboard());
+            let behind = match side {
+                Side::White => from.north(),
+                Side::Black => from.south(),
+            };
+            debug_assert_eq!(
+                behind,
+                match side {
+                    Side::White => to.south(),
+                    Side::Black => to.north(),
+                }
+            );
+            self.set_en_passant(behind);
+        }
+
+        self.revoke_castling_rights(mv.from(), mv.to());
+        self.flip_side();
+        self.debug_validate_hash();
+        undo
+    }
+
+    /// Takes back `mv`, which has to be the last move played with [`Node::make`].
+    pub fn unmake(&mut self, mv: Move, undo: Undo) {
+        self.side = self.side.opponent();
+        if self.side == Side::Black {
+            self.move_clock -= 1;
+        }
+        self.castling_rights = undo.castling_rights;
+        self.en_passant_square = undo.en_passant_square;
+        self.halfmove_clock = undo.halfmove_clock;
+        self.unmove_pieces(mv, undo.captured);
+        self.hash = undo.hash;
+        self.pawn_hash = undo.pawn_hash;
+        self.debug_validate_hash();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        castling_rights::CastlingSide,
+        coord::Square,
+        moves::{Move, MoveType},
+        perft::node::Node,
+        piece::Side,
+        position::Position,
+    };
+
+    fn check_make_unmake(node: &mut Node, depth: u8) {
+        if depth == 0 {
+            return;
+        }
+        let original = node.clone();
+        for &mv in &node.legal_moves() {
+            let undo = node.make(mv);
+            node.debug_validate_occupancies();
+            assert_eq!(*node, original.make_move(mv));
+            check_make_unmake(node, depth - 1);
+            node.unmake(mv, undo);
+            assert_eq!(*node, original, "{:?}", mv);
+        }
+    }
+
+    fn leaves(node: &mut Node, depth: u8) -> usize {
+        let moves = node.legal_moves();
+        if depth == 1 {
+            return moves.len();
+        }
+        moves
+            .iter()
+            .map(|&mv| {
+                let undo = node.make(mv);
+                let n = leaves(node, depth - 1);
+                node.unmake(mv, undo);
+                n
+            })
+            .sum()
+    }
+
+    #[test]
+    fn known_leaf_counts() {
+        assert_eq!(leaves(&mut Node::POSITION_1.clone(), 4), 197_281);
+        assert_eq!(leaves(&mut Node::POSITION_2.clone(), 3), 97_862);
+        assert_eq!(leaves(&mut Node::POSITION_3.clone(), 4), 43_238);
+        assert_eq!(leaves(&mut Node::POSITION_4.clone(), 3), 9_467);
+        assert_eq!(leaves(&mut Node::POSITION_5.clone(), 3), 62_379);
+        assert_eq!(leaves(&mut Node::POSITION_6.clone(), 3), 89_890);
+    }
+
+    #[test]
+    fn make_unmake_roundtrip() {
+        check_make_unmake(&mut Node::POSITION_2.clone(), 3);
+        check_make_unmake(&mut Node::POSITION_3.clone(), 3);
+        check_make_unmake(&mut Node::POSITION_4.clone(), 3);
+        check_make_unmake(&mut Node::POSITION_5.clone(), 2);
+    }
+
+    #[test]
+    fn hash_transpositions() {
+        let play = |node: &Node, moves: &[(Square, Square, MoveType)]| {
+            moves.iter().fold(node.clone(), |node, &(from, to, kind)| {
+                node.make_move(Move::new(from, to, kind))
+            })
+        };
+        let start = Node::POSITION_1;
+
+        // Back to the start position, only the clocks differ
+        let knights = play(
+            &start,
+            &[
+                (Square::G1, Square::F3, MoveType::Quiet),
+                (Square::G8, Square::F6, MoveType::Quiet),
+                (Square::F3, Square::G1, MoveType::Quiet),
+                (Square::F6, Square::G8, MoveType::Quiet),
+            ],
+        );
+        assert_ne!(knights, start);
+        assert_eq!(knights.hash, start.hash);
+
+        // Same pieces, different side to move
+        let white_only = play(
+            &start,
+            &[
+                (Square::G1, Square::F3, MoveType::Quiet),
+                (Square::G8, Square::F6, MoveType::Quiet),
+                (Square::F3, Square::G1, MoveType::Quiet),
+            ],
+        );
+        let black_only = play(&start, &[(Square::G1, Square::F3, MoveType::Quiet)]);
+        assert_ne!(white_only.hash, black_only.hash);
+        // The pawn hash only cares about pawns
+        assert_eq!(white_only.pawn_hash, start.pawn_hash);
+        assert_eq!(black_only.pawn_hash, start.pawn_hash);
+
+        // The en passant square is part of the hash
+        let double = play(&start, &[(Square::E2, Square::E4, MoveType::DoublePush)]);
+        let mut single = play(
+            &start,
+            &[
+                (Square::E2, Square::E3, MoveType::Quiet),
+                (Square::G8, Square::F6, MoveType::Quiet),
+                (Square::E3, Square::E4, MoveType::Quiet),
+                (Square::F6, Square::G8, MoveType::Quiet),
+            ],
+        );
+        single.flip_side();
+        assert_eq!(single.to_position().pieces, double.to_position().pieces);
+        assert_ne!(single.hash, double.hash);
+        single.set_en_passant(Square::E3.to_bitboard());
+        assert_eq!(single.hash, double.hash);
+        assert_eq!(single.pawn_hash, double.pawn_hash);
+        assert_ne!(double.pawn_hash, start.pawn_hash);
+
+        // Losing castling rights changes the hash
+        let rook = play(
+            &Node::POSITION_2,
+            &[
+                (Square::H1, Square::G1, MoveType::Quiet),
+                (Square::A6, Square::B7, MoveType::Quiet),
+                (Square::G1, Square::H1, MoveType::Quiet),
+                (Square::B7, Square::A6, MoveType::Quiet),
+            ],
+        );
+        assert_eq!(
+            rook.to_position().pieces,
+            Node::POSITION_2.to_position().pieces
+        );
+        assert_ne!(rook.hash, Node::POSITION_2.hash);
+    }
+
+    #[test]
+    fn state_updates() {
+        let node = Node::POSITION_2;
+        let castle = Move::new(Square::E1, Square::G1, MoveType::CastleKingside);
+        let after = node.make_move(castle).to_position();
+        assert_eq!(
+            after.to_fen(),
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R4RK1 b kq - 1 1"
+        );
+
+        let capture = Move::new(Square::A6, Square::E2, MoveType::Capture);
+        let after = node.make_move(castle).make_move(capture);
+        assert_eq!(after.halfmove_clock, 0);
+        assert_eq!(after.move_clock, 1);
+
+        let after =
+            Node::POSITION_1.make_move(Move::new(Square::E2, Square::E4, MoveType::DoublePush));
+        assert_eq!(
+            after.to_position().to_fen(),
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1"
+        );
+
+        // Capturing a rook revokes the opponent's rights on that side
+        let pos = Position::parse_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 5 10").unwrap();
+        let after = pos
+            .to_node()
+            .make_move(Move::new(Square::A1, Square::A8, MoveType::Capture));
+        assert!(
+            !after
+                .castling_rights
+                .can_castle(Side::White, CastlingSide::QueenSide)
+        );
+        assert!(
+            after
+                .castling_rights
+                .can_castle(Side::White, CastlingSide::KingSide)
+        );
+        assert!(
+            !after
+                .castling_rights
+                .can_castle(Side::Black, CastlingSide::QueenSide)
+        );
+        assert!(
+            after
+                .castling_rights
+                .can_castle(Side::Black, CastlingSide::KingSide)
+        );
+        assert_eq!(after.halfmove_clock, 0);
+        let after = after.make_move(Move::new(Square::E8, Square::E7, MoveType::Quiet));
+        assert_eq!(
+            after.to_position().to_fen(),
+            "R6r/4k3/8/8/8/8/8/4K2R w K - 1 11"
+        );
+    }
+}
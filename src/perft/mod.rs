@@ -1,2 +1,9 @@
+mod all;
+pub mod cache;
+mod make;
+mod movegen;
 pub mod node; // TODO not pub
 pub mod pawn; // TODO not pub
+mod see;
+pub mod stats;
+pub mod suite;
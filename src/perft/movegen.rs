@@ -0,0 +1,536 @@
+use crate::{
+    bitboard::BitBoard,
+    castling_rights::CastlingSide,
+    coord::{Rank, Square},
+    moves::{Move, MoveList, MoveType},
+    piece::{Piece, PieceType, Side},
+    pieces::{
+        bishop::bishop_moves,
+        king::king_moves,
+        knight::knight_moves,
+        line::{between, line},
+        pawn::pawn_attacks,
+        queen::queen_moves,
+        rook::rook_moves,
+    },
+};
+
+use super::node::Node;
+
+const fn shift(bb: BitBoard, by: i8) -> BitBoard {
+    if by > 0 {
+        bb.lshift(by)
+    } else {
+        bb.rshift(-by)
+    }
+}
+
+// Walks back from `to` to the square a pawn came from
+const fn origin(to: Square, by: i8) -> Square {
+    Square::from_index((to.to_index() as i8 - by) as u8).unwrap()
+}
+
+struct PawnDirections {
+    forward: i8,
+    east: i8,
+    west: i8,
+    promotion_rank: BitBoard,
+    double_push_rank: BitBoard,
+}
+
+impl PawnDirections {
+    const WHITE: Self = PawnDirections {
+        forward: 8,
+        east: 9,
+        west: 7,
+        promotion_rank: BitBoard::R8,
+        double_push_rank: Rank::R4.to_bitboard(),
+    };
+    const BLACK: Self = PawnDirections {
+        forward: -8,
+        east: -7,
+        west: -9,
+        promotion_rank: BitBoard::R1,
+        double_push_rank: Rank::R5.to_bitboard(),
+    };
+    const fn new(side: Side) -> &'static Self {
+        match side {
+            Side::White => &Self::WHITE,
+            Side::Black => &Self::BLACK,
+        }
+    }
+}
+
+// The squares involved in castling. The king has to be safe on its source, transit, and
+// destination squares.
+struct CastlingPath {
+    king: Square,
+    to: Square,
+    rook: Square,
+    empty: BitBoard,
+    transit: Square,
+}
+
+const fn castling_path(side: Side, castling_side: CastlingSide) -> CastlingPath {
+    let path = match castling_side {
+        CastlingSide::KingSide => CastlingPath {
+            king: Square::E1,
+            to: Square::G1,
+            rook: Square::H1,
+            empty: Square::F1.to_bitboard().union(Square::G1.to_bitboard()),
+            transit: Square::F1,
+        },
+        CastlingSide::QueenSide => CastlingPath {
+            king: Square::E1,
+            to: Square::C1,
+            rook: Square::A1,
+            empty: Square::B1
+                .to_bitboard()
+                .union(Square::C1.to_bitboard())
+                .union(Square::D1.to_bitboard()),
+            transit: Square::D1,
+        },
+    };
+    match side {
+        Side::White => path,
+        Side::Black => CastlingPath {
+            king: path.king.vflip(),
+            to: path.to.vflip(),
+            rook: path.rook.vflip(),
+            empty: path.empty.vflip(),
+            transit: path.transit.vflip(),
+        },
+    }
+}
+
+// Everything needed to generate strictly legal moves, computed once up front
+struct CheckInfo {
+    king: Square,
+    checkers: BitBoard,
+    // Our pieces that are the only thing between our king and an enemy slider
+    pinned: BitBoard,
+    // Squares that a non-king move has to land on. Everything when not in check, the checker and
+    // the squares between it and the king when in single check, and nothing in double check.
+    evasion_mask: BitBoard,
+}
+
+impl Node {
+    /// All legal moves for the side to move.
+    pub fn legal_moves(&self) -> MoveList {
+        let mut moves = MoveList::new();
+        self.for_each_legal_move(|mv| moves.push(mv));
+        moves
+    }
+
+    /// The legal captures and promotions for the side to move, for quiescence search.
+    pub fn legal_captures(&self) -> MoveList {
+        let mut moves = MoveList::new();
+        self.for_each_legal_capture(|mv| moves.push(mv));
+        moves
+    }
+
+    /// Calls `f` for every legal move.
+    /// Rather than playing each move and checking if it leaves the king in check, this computes
+    /// the checkers and pinned pieces up front, and only generates moves that respect them.
+    pub fn for_each_legal_move<F: FnMut(Move)>(&self, f: F) {
+        self.generate_legal(true, f);
+    }
+
+    /// Calls `f` for every legal capture, including en passant, and every legal promotion.
+    pub fn for_each_legal_capture<F: FnMut(Move)>(&self, f: F) {
+        self.generate_legal(false, f);
+    }
+
+    fn generate_legal<F: FnMut(Move)>(&self, quiets: bool, mut f: F) {
+        let side = self.side;
+        let info = self.check_info();
+        let enemies = self.occupancy(side.opponent());
+        // Captures land on enemy pieces, so leaving out the quiet moves is just a matter of
+        // narrowing the targets. Pawns are the exception, they promote on empty squares too.
+        let targets = if quiets {
+            self.occupancy(side).complement()
+        } else {
+            enemies
+        };
+
+        // The king is removed from the blockers, otherwise it could step back along the ray of a
+        // slider that is checking it
+        let blockers = self.occupancy_total.difference(info.king.to_bitboard());
+        for (to, to_bb) in king_moves(info.king).intersect(targets) {
+            if !self
+                .attackers_to(to, blockers)
+                .intersects(self.occupancy(side.opponent()))
+            {
+                let kind = if enemies.intersects(to_bb) {
+                    MoveType::Capture
+                } else {
+                    MoveType::Quiet
+                };
+                f(Move::new(info.king, to, kind));
+            }
+        }
+
+        // In double check, only the king can move
+        if info.checkers.popcount() > 1 {
+            return;
+        }
+
+        let mut f = |mv: Move| {
+            if !info.pinned.contains(mv.from()) || line(info.king, mv.from()).contains(mv.to()) {
+                f(mv)
+            }
+        };
+        self.for_pawn_moves(info.evasion_mask, quiets, &mut f);
+        // En passant removes two pieces from a rank at once, which the pin detection doesn't
+        // cover. It's rare enough that we can just fall back to make-and-test.
+        self.for_en_passant_moves(&mut |mv| {
+            if self.is_legal(mv) {
+                f(mv)
+            }
+        });
+        self.for_non_pawn_moves(info.evasion_mask.intersect(targets), &mut f);
+        if quiets && info.checkers.is_empty() {
+            self.for_castling_moves(&mut f);
+        }
+    }
+
+    fn check_info(&self) -> CheckInfo {
+        let side = self.side;
+        let them = side.opponent();
+        let king = self.king_square(side);
+        let enemy = |piece_type| self.piece(Piece::from_side_piece(them, piece_type));
+        let queens = enemy(PieceType::Queen);
+        let diagonal = enemy(PieceType::Bishop).union(queens);
+        let orthogonal = enemy(PieceType::Rook).union(queens);
+
+        let checkers = self.attackers_by(them, king);
+
+        // X-ray from the king through our own pieces, to find the sliders that could pin them
+        let snipers = bishop_moves(king, self.occupancy(them))
+            .intersect(diagonal)
+            .union(rook_moves(king, self.occupancy(them)).intersect(orthogonal));
+        let mut pinned = BitBoard::EMPTY;
+        for (sniper, _) in snipers {
+            let blockers = between(king, sniper).intersect(self.occupancy_total);
+            if blockers.popcount() == 1 {
+                pinned = pinned.union(blockers.intersect(self.occupancy(side)));
+            }
+        }
+
+        let evasion_mask = match checkers.get_square() {
+            None => BitBoard::FULL,
+            Some(checker) if checkers.popcount() == 1 => checkers.union(between(king, checker)),
+            Some(_) => BitBoard::EMPTY,
+        };
+
+        CheckInfo {
+            king,
+            checkers,
+            pinned,
+            evasion_mask,
+        }
+    }
+
+    // Make-and-test: play the move, and see if it leaves our king in check
+    fn is_legal(&self, mv: Move) -> bool {
+        let mut node = self.clone();
+        node.move_pieces(mv);
+        !node.king_attacked(self.side)
+    }
+
+    /// Calls `f` for every pseudo-legal move, i.e. moves that might leave the king in check.
+    /// Castling moves are the exception, they are only generated if they are legal.
+    pub fn for_each_pseudo_legal_move<F: FnMut(Move)>(&self, mut f: F) {
+        self.for_pawn_moves(BitBoard::FULL, true, &mut f);
+        self.for_en_passant_moves(&mut f);
+        let king = Piece::from_side_piece(self.side, PieceType::King);
+        self.for_piece_moves(king, king_moves, BitBoard::FULL, &mut f);
+        self.for_non_pawn_moves(BitBoard::FULL, &mut f);
+        self.for_castling_moves(&mut f);
+    }
+
+    // Knight, bishop, rook, and queen moves
+    fn for_non_pawn_moves<F: FnMut(Move)>(&self, targets: BitBoard, f: &mut F) {
+        let side = self.side;
+        let blockers = self.occupancy_total;
+        let bishop = |sq| bishop_moves(sq, blockers);
+        let rook = |sq| rook_moves(sq, blockers);
+        let queen = |sq| queen_moves(sq, blockers);
+        let piece = |piece_type| Piece::from_side_piece(side, piece_type);
+        self.for_piece_moves(piece(PieceType::Knight), knight_moves, targets, f);
+        self.for_piece_moves(piece(PieceType::Bishop), bishop, targets, f);
+        self.for_piece_moves(piece(PieceType::Rook), rook, targets, f);
+        self.for_piece_moves(piece(PieceType::Queen), queen, targets, f);
+    }
+
+    fn for_piece_moves<G: Fn(Square) -> BitBoard, F: FnMut(Move)>(
+        &self,
+        piece: Piece,
+        movegen: G,
+        targets: BitBoard,
+        f: &mut F,
+    ) {
+        let enemies = self.occupancy(piece.side().opponent());
+        for (from, _) in self.piece(piece) {
+            let targets = movegen(from)
+                .difference(self.occupancy(piece.side()))
+                .intersect(targets);
+            for (to, to_bb) in targets {
+                let kind = if enemies.intersects(to_bb) {
+                    MoveType::Capture
+                } else {
+                    MoveType::Quiet
+                };
+                f(Move::new(from, to, kind));
+            }
+        }
+    }
+
+    // Excluding en passant. Without `quiets`, only captures and promotions.
+    fn for_pawn_moves<F: FnMut(Move)>(&self, targets: BitBoard, quiets: bool, f: &mut F) {
+        let side = self.side;
+        let dir = PawnDirections::new(side);
+        let pawns = self.piece(Piece::from_side_piece(side, PieceType::Pawn));
+        let empty = self.occupancy_total.complement();
+        let enemies = self.occupancy(side.opponent()).intersect(targets);
+
+        let single = shift(pawns, dir.forward).intersect(empty);
+        let double = shift(single, dir.forward)
+            .intersect(empty)
+            .intersect(dir.double_push_rank)
+            .intersect(targets);
+        let single = single.intersect(targets);
+
+        if quiets {
+            for (to, _) in single.difference(dir.promotion_rank) {
+                f(Move::new(origin(to, dir.forward), to, MoveType::Quiet));
+            }
+            for (to, _) in double {
+                f(Move::new(
+                    origin(to, 2 * dir.forward),
+                    to,
+                    MoveType::DoublePush,
+                ));
+            }
+        }
+        for (to, _) in single.intersect(dir.promotion_rank) {
+            let from = origin(to, dir.forward);
+            for kind in [
+                MoveType::PromoteQueen,
+                MoveType::PromoteKnight,
+                MoveType::PromoteRook,
+                MoveType::PromoteBishop,
+            ] {
+                f(Move::new(from, to, kind));
+            }
+        }
+
+        // The rim is the file from which a pawn cannot capture in that direction
+        for (by, rim) in [(dir.east, BitBoard::FH), (dir.west, BitBoard::FA)] {
+            let targets = shift(pawns.difference(rim), by).intersect(enemies);
+            for (to, _) in targets.difference(dir.promotion_rank) {
+                f(Move::new(origin(to, by), to, MoveType::Capture));
+            }
+            for (to, _) in targets.intersect(dir.promotion_rank) {
+                let from = origin(to, by);
+                for kind in [
+                    MoveType::PromoteCaptureQueen,
+                    MoveType::PromoteCaptureKnight,
+                    MoveType::PromoteCaptureRook,
+                    MoveType::PromoteCaptureBishop,
+                ] {
+                    f(Move::new(from, to, kind));
+                }
+            }
+        }
+    }
+
+    fn for_en_passant_moves<F: FnMut(Move)>(&self, f: &mut F) {
+        let side = self.side;
+        let dir = PawnDirections::new(side);
+        let pawns = self.piece(Piece::from_side_piece(side, PieceType::Pawn));
+        if let Some(to) = self.en_passant_square.to_square() {
+            let victim = origin(to, dir.forward).to_bitboard();
+            let enemy_pawn = Piece::from_side_piece(side.opponent(), PieceType::Pawn);
+            if self.piece(enemy_pawn).intersects(victim) {
+                for (from, _) in pawn_attacks(side.opponent(), to).intersect(pawns) {
+                    f(Move::new(from, to, MoveType::CaptureEnPassant));
+                }
+            }
+        }
+    }
+
+    fn for_castling_moves<F: FnMut(Move)>(&self, f: &mut F) {
+        let side = self.side;
+        let king = self.piece(Piece::from_side_piece(side, PieceType::King));
+        let rooks = self.piece(Piece::from_side_piece(side, PieceType::Rook));
+        let mut in_check = None;
+        for (castling_side, kind) in [
+            (CastlingSide::KingSide, MoveType::CastleKingside),
+            (CastlingSide::QueenSide, MoveType::CastleQueenside),
+        ] {
+            let path = castling_path(side, castling_side);
+            if self.castling_rights.can_castle(side, castling_side)
+                && king.contains(path.king)
+                && rooks.contains(path.rook)
+                && !self.occupancy_total.intersects(path.empty)
+                && !*in_check.get_or_insert_with(|| self.king_attacked(side))
+                && !self.square_is_attacked_by(side.opponent(), path.transit)
+                && !self.square_is_attacked_by(side.opponent(), path.to)
+            {
+                f(Move::new(path.king, path.to, kind));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        coord::Square,
+        moves::{Move, MoveType},
+        perft::node::Node,
+        position::Position,
+    };
+
+    // Compares the legal moves against the pseudo-legal moves that pass make-and-test, in every
+    // node of the tree up to `depth`
+    fn compare_with_make_and_test(node: &Node, depth: u8) {
+        let mut reference = Vec::new();
+        node.for_each_pseudo_legal_move(|mv| {
+            if node.is_legal(mv) {
+                reference.push(mv)
+            }
+        });
+        let moves = node.legal_moves();
+        assert_eq!(
+            moves.len(),
+            reference.len(),
+            "{}",
+            node.to_position().to_fen()
+        );
+        assert!(reference.iter().all(|&mv| moves.contains(mv)));
+        if depth > 0 {
+            for &mv in &moves {
+                compare_with_make_and_test(&node.make_move(mv), depth - 1);
+            }
+        }
+    }
+
+    #[test]
+    fn masks_match_make_and_test() {
+        compare_with_make_and_test(&Node::POSITION_2, 2);
+        compare_with_make_and_test(&Node::POSITION_3, 3);
+        compare_with_make_and_test(&Node::POSITION_4, 2);
+        compare_with_make_and_test(&Node::POSITION_5, 2);
+    }
+
+    #[test]
+    fn pins_and_checks() {
+        // Pinned bishop can only move along the pin
+        assert_eq!(count("4k3/8/8/8/1b6/8/3B4/4K3 w - - 0 1"), 4 + 2);
+        // Pinned knight can't move at all
+        assert_eq!(count("4k3/4r3/8/8/8/8/4N3/4K3 w - - 0 1"), 4);
+        // Double check, only the king moves
+        assert_eq!(count("4k3/8/8/8/1b6/8/4r3/R3K3 w - - 0 1"), 3);
+        // Single check can be blocked or captured
+        assert_eq!(count("4k3/8/8/8/1b6/R7/8/1R2K3 w - - 0 1"), 4 + 1 + 1);
+        // The king can't retreat along the checking ray
+        assert_eq!(count("4k3/4r3/8/8/8/8/8/4K3 w - - 0 1"), 4);
+    }
+
+    fn count(fen: &str) -> usize {
+        Position::parse_fen(fen)
+            .unwrap()
+            .to_node()
+            .legal_moves()
+            .len()
+    }
+
+    fn compare_captures(node: &Node, depth: u8) {
+        let moves = node.legal_moves();
+        let expected: Vec<Move> = moves
+            .iter()
+            .copied()
+            .filter(|mv| mv.is_capture() || mv.promotion().is_some())
+            .collect();
+        assert_eq!(
+            node.legal_captures().as_slice(),
+            expected.as_slice(),
+            "{}",
+            node.to_position().to_fen()
+        );
+        if depth > 0 {
+            for &mv in &moves {
+                compare_captures(&node.make_move(mv), depth - 1);
+            }
+        }
+    }
+
+    #[test]
+    fn captures_match_legal_moves() {
+        compare_captures(&Node::POSITION_2, 2);
+        compare_captures(&Node::POSITION_3, 3);
+        compare_captures(&Node::POSITION_4, 2);
+        compare_captures(&Node::POSITION_5, 2);
+    }
+
+    #[test]
+    fn standard_positions() {
+        assert_eq!(Node::POSITION_1.legal_moves().len(), 20);
+        assert_eq!(Node::POSITION_2.legal_moves().len(), 48);
+        assert_eq!(Node::POSITION_3.legal_moves().len(), 14);
+        assert_eq!(Node::POSITION_4.legal_moves().len(), 6);
+        assert_eq!(Node::POSITION_5.legal_moves().len(), 44);
+        assert_eq!(Node::POSITION_6.legal_moves().len(), 46);
+    }
+
+    #[test]
+    fn black_to_move() {
+        // Mirrored version of position 4
+        assert_eq!(
+            count("r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1"),
+            6
+        );
+    }
+
+    #[test]
+    fn castling() {
+        let pos = Node::POSITION_2.legal_moves();
+        assert!(pos.contains(Move::new(Square::E1, Square::G1, MoveType::CastleKingside)));
+        assert!(pos.contains(Move::new(Square::E1, Square::C1, MoveType::CastleQueenside)));
+        // Through check
+        assert_eq!(count("3rk3/8/8/8/8/8/8/R3K3 w Q - 0 1"), 3 + 10);
+        // Out of check
+        assert_eq!(count("4k3/8/8/8/8/8/4r3/R3K2R w KQ - 0 1"), 3);
+        // Queen side rook may pass an attacked square
+        assert_eq!(count("1r2k3/8/8/8/8/8/8/R3K3 w Q - 0 1"), 5 + 10 + 1);
+    }
+
+    #[test]
+    fn en_passant() {
+        let pos = Position::parse_fen("8/8/3p4/KPp4r/1R2Pp1k/8/6P1/8 w - c6 0 2").unwrap();
+        let moves = pos.to_node().legal_moves();
+        assert!(!moves.contains(Move::new(
+            Square::B5,
+            Square::C6,
+            MoveType::CaptureEnPassant
+        )));
+        // Horizontally pinned en passant
+        assert_eq!(count("8/8/8/KPp4r/8/8/8/7k w - c6 0 1"), 3 + 1);
+        assert_eq!(count("8/8/8/1KPp3r/8/8/8/7k w - d6 0 1"), 6 + 1);
+        let black = Position::parse_fen("8/8/8/8/4pP2/8/8/K3k3 b - f3 0 1").unwrap();
+        assert!(black.to_node().legal_moves().contains(Move::new(
+            Square::E4,
+            Square::F3,
+            MoveType::CaptureEnPassant
+        )));
+    }
+
+    #[test]
+    fn promotions() {
+        // One capture-promotion and one push-promotion, for all four pieces
+        assert_eq!(count("1n5k/P7/8/8/8/8/8/K7 w - - 0 1"), 3 + 8);
+        assert_eq!(count("K7/8/8/8/8/8/p7/1N5k b - - 0 1"), 3 + 8);
+    }
+}
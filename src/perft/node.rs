@@ -2,23 +2,24 @@ use crate::{
     bitboard::BitBoard,
     castling_rights::CastlingRights,
     coord::{Rank, Square},
+    moves::{Move, MoveType},
     piece::{Piece, PieceType, Side},
     pieces::{
-        bishop::bishop_moves, king::king_moves, knight::knight_moves, pawn::PawnAttacks,
+        bishop::bishop_moves, king::king_moves, knight::knight_moves, pawn::pawn_attacks,
         rook::rook_moves,
     },
     position::Position,
     zobrist_table::ZOBRIST_TABLE,
 };
 
-#[derive(Clone)]
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub struct EnPassantSquare {
     mask: BitBoard,
 }
 
 impl EnPassantSquare {
     const MASK: BitBoard = Rank::R6.to_bitboard().union(Rank::R3.to_bitboard());
-    const fn to_index(&self) -> usize {
+    const fn to_index(self) -> usize {
         self.mask.to_bits().trailing_zeros() as usize
     }
     const fn debug_assert_valid(bb: BitBoard) {
@@ -47,12 +48,12 @@ impl EnPassantSquare {
     const fn from_square(sq: Square) -> Self {
         Self::from_bitboard(sq.to_bitboard())
     }
-    const fn to_square(&self) -> Option<Square> {
+    pub const fn to_square(&self) -> Option<Square> {
         self.mask.get_square()
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Eq)]
 pub struct Node {
     pub pieces: [BitBoard; 12], // TODO probably just unroll this
     pub side: Side,
@@ -63,6 +64,21 @@ pub struct Node {
     // TODO For now, A1 == 0 means no en passant possible. A little ugly, but if it turns out to
     // work I'll wrap it up in a cleaner API.
     pub en_passant_square: EnPassantSquare,
+    pub halfmove_clock: u8,
+    pub move_clock: usize,
+    /// Zobrist hash of the pieces, side to move, castling rights, and en passant square.
+    /// Kept up to date incrementally by the mutation helpers below.
+    pub hash: u64,
+    /// Zobrist hash of just the pawns, for caching pawn structure evaluation. Kept up to date like
+    /// [`Node::hash`].
+    pub pawn_hash: u64,
+}
+
+impl std::fmt::Debug for Node {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", self.to_position().to_fen())?;
+        std::fmt::Debug::fmt(&self.to_position(), f)
+    }
 }
 
 impl Node {
@@ -78,28 +94,62 @@ impl Node {
             Side::Black => self.occupancy_black,
         }
     }
-    fn hash(&self) -> u64 {
-        // TODO: This should be done incrementally, probably
+    // Hash of the non-piece state
+    const fn state_hash(
+        side: Side,
+        castling_rights: &CastlingRights,
+        en_passant_square: EnPassantSquare,
+    ) -> u64 {
+        let mut hash = ZOBRIST_TABLE.hash_castling_rights(castling_rights);
+        hash ^= ZOBRIST_TABLE.hash_side(side);
+        if !en_passant_square.to_bitboard().is_empty() {
+            hash ^= ZOBRIST_TABLE.hash_en_passant_square(en_passant_square.to_index());
+        }
+        hash
+    }
+    /// Recomputes the hash from scratch, which the incremental [`Node::hash`] should always match.
+    pub fn hash_from_scratch(&self) -> u64 {
+        let mut hash = Self::state_hash(self.side, &self.castling_rights, self.en_passant_square);
+        for piece in Piece::PIECES {
+            for (sq, _) in self.piece(piece) {
+                hash ^= ZOBRIST_TABLE.hash_piece(piece, sq);
+            }
+        }
+        hash
+    }
+    /// Recomputes the pawn hash from scratch, which [`Node::pawn_hash`] should always match.
+    pub fn pawn_hash_from_scratch(&self) -> u64 {
         let mut hash = 0;
-        for pc_ix in 0..Piece::NUM_PIECES {
-            for (sq, _) in self.pieces[pc_ix as usize] {
-                hash ^= ZOBRIST_TABLE.hash_piece(Piece::from_index(pc_ix).unwrap(), sq);
+        for piece in [Piece::WhitePawn, Piece::BlackPawn] {
+            for (sq, _) in self.piece(piece) {
+                hash ^= ZOBRIST_TABLE.hash_piece(piece, sq);
             }
         }
-        hash ^= ZOBRIST_TABLE.hash_en_passant_square(self.en_passant_square.to_index());
-        hash ^= ZOBRIST_TABLE.hash_castling_rights(&self.castling_rights);
-        hash ^= ZOBRIST_TABLE.hash_side(self.side);
         hash
     }
+    pub fn debug_validate_hash(&self) {
+        debug_assert_eq!(self.hash, self.hash_from_scratch());
+        debug_assert_eq!(self.pawn_hash, self.pawn_hash_from_scratch());
+    }
     pub const fn from_position(pos: &Position) -> Node {
         let mut pieces = [BitBoard::EMPTY; 12];
         let mut occupancy_white = BitBoard::EMPTY;
         let mut occupancy_black = BitBoard::EMPTY;
+        let en_passant_square = match pos.en_passant_square {
+            Some(sq) => EnPassantSquare::from_square(sq),
+            None => EnPassantSquare::empty(),
+        };
+        let mut hash = Self::state_hash(pos.side, &pos.castling_rights, en_passant_square);
+        let mut pawn_hash = 0;
         let mut i = 0;
         while i < 64 {
             let sq = Square::from_index(i).unwrap();
             if let Some(piece) = pos.get_piece(sq) {
                 pieces[piece as usize].set_assign(sq);
+                hash ^= ZOBRIST_TABLE.hash_piece(piece, sq);
+                if let Piece::WhitePawn | Piece::BlackPawn = piece {
+                    pawn_hash ^= ZOBRIST_TABLE.hash_piece(piece, sq);
+                }
                 match piece.side() {
                     Side::White => occupancy_white.set_assign(sq),
                     Side::Black => occupancy_black.set_assign(sq),
@@ -114,66 +164,86 @@ impl Node {
             occupancy_black,
             occupancy_total: occupancy_black.union(occupancy_white),
             castling_rights: pos.castling_rights,
-            en_passant_square: match pos.en_passant_square {
-                Some(sq) => EnPassantSquare::from_square(sq),
-                None => EnPassantSquare::empty(),
-            },
+            en_passant_square,
+            halfmove_clock: pos.halfmove_clock,
+            move_clock: pos.move_clock,
+            hash,
+            pawn_hash,
         }
     }
-    pub fn square_is_attacked_by(&self, side: Side, sq: Square, bb: BitBoard) -> bool {
-        debug_assert_eq!(sq.to_bitboard(), bb);
-
-        if knight_moves(sq).intersects(self.piece(Piece::from_side_piece(side, PieceType::Knight)))
-        {
-            return true;
-        }
-
-        // TODO remove the pawn attacks module
-        if self.pawn_attacks(side).threat().intersects(bb) {
-            return true;
+    pub fn to_position(&self) -> Position {
+        let mut pieces = [None; 64];
+        for piece in Piece::PIECES {
+            for (sq, _) in self.piece(piece) {
+                pieces[sq.to_index() as usize] = Some(piece);
+            }
         }
-
-        let bishops: BitBoard = self.piece(Piece::from_side_piece(side, PieceType::Bishop));
-        let queens: BitBoard = self.piece(Piece::from_side_piece(side, PieceType::Queen));
-        if bishop_moves(sq, self.occupancy_total).intersects(bishops.union(queens)) {
-            return true;
+        Position {
+            pieces,
+            side: self.side,
+            castling_rights: self.castling_rights,
+            en_passant_square: self.en_passant_square.to_square(),
+            halfmove_clock: self.halfmove_clock,
+            move_clock: self.move_clock,
         }
+    }
+    /// Whether any of `side`'s pieces attacks `sq`.
+    pub fn square_is_attacked_by(&self, side: Side, sq: Square) -> bool {
+        self.attackers_by(side, sq).is_nonempty()
+    }
 
-        let rooks: BitBoard = self.piece(Piece::from_side_piece(side, PieceType::Rook));
-        if rook_moves(sq, self.occupancy_total).intersects(rooks.union(queens)) {
-            return true;
-        }
+    /// `side`'s pieces that attack `sq`.
+    pub fn attackers_by(&self, side: Side, sq: Square) -> BitBoard {
+        self.side_attackers(side, sq, self.occupancy_total)
+    }
 
-        if king_moves(sq).intersects(self.piece(Piece::from_side_piece(side, PieceType::King))) {
-            return true;
-        }
+    /// The pieces of both sides that attack `sq`. Sliders are blocked by `occupancy` rather than
+    /// the actual board, so that removing pieces from it uncovers the x-ray attackers behind them.
+    /// Pieces missing from `occupancy` may still be included.
+    pub fn attackers_to(&self, sq: Square, occupancy: BitBoard) -> BitBoard {
+        self.side_attackers(Side::White, sq, occupancy)
+            .union(self.side_attackers(Side::Black, sq, occupancy))
+    }
 
-        false
+    fn side_attackers(&self, side: Side, sq: Square, occupancy: BitBoard) -> BitBoard {
+        let piece = |piece_type| self.piece(Piece::from_side_piece(side, piece_type));
+        let queens = piece(PieceType::Queen);
+        knight_moves(sq)
+            .intersect(piece(PieceType::Knight))
+            .union(king_moves(sq).intersect(piece(PieceType::King)))
+            // A pawn attacks the squares that an opposing pawn on `sq` would attack
+            .union(pawn_attacks(side.opponent(), sq).intersect(piece(PieceType::Pawn)))
+            .union(bishop_moves(sq, occupancy).intersect(piece(PieceType::Bishop).union(queens)))
+            .union(rook_moves(sq, occupancy).intersect(piece(PieceType::Rook).union(queens)))
     }
 
     pub fn white_king_attacked(&self) -> bool {
-        let bb = self.piece(Piece::WhiteKing);
-        debug_assert!(!bb.is_empty());
-        // TODO Obviously not ideal. Maybe have a nonempty bitboard, that would optimize away with
-        // unwrap. Big change though.
-        let sq = unsafe { bb.get_square().unwrap_unchecked() };
-        self.square_is_attacked_by(Side::Black, sq, bb)
+        self.king_attacked(Side::White)
     }
 
-    pub fn black_king_attacked(&self) -> bool {
-        let bb = self.piece(Piece::BlackKing);
+    pub fn king_square(&self, side: Side) -> Square {
+        let bb = self.piece(Piece::from_side_piece(side, PieceType::King));
         debug_assert!(!bb.is_empty());
-        // TODO Obviously not ideal. Maybe have a nonempty bitboard, that would optimize away with
-        // unwrap. Big change though.
-        let sq = unsafe { bb.get_square().unwrap_unchecked() };
-        self.square_is_attacked_by(Side::White, sq, bb)
+        unsafe { bb.get_square().unwrap_unchecked() }
     }
-    // TODO remove
-    fn pawn_attacks(&self, side: Side) -> PawnAttacks {
-        match side {
-            Side::White => PawnAttacks::white(self.piece(Piece::WhitePawn), self.occupancy_black),
-            Side::Black => PawnAttacks::black(self.piece(Piece::BlackPawn), self.occupancy_white),
-        }
+
+    pub fn king_attacked(&self, side: Side) -> bool {
+        self.square_is_attacked_by(side.opponent(), self.king_square(side))
+    }
+
+    /// Finds which of `side`'s pieces, if any, occupies the square in `sq_bb`.
+    pub fn find_piece(&self, side: Side, sq_bb: BitBoard) -> Option<Piece> {
+        let pieces = match side {
+            Side::White => Piece::WHITE_PIECES,
+            Side::Black => Piece::BLACK_PIECES,
+        };
+        pieces
+            .into_iter()
+            .find(|&piece| self.piece(piece).intersects(sq_bb))
+    }
+
+    pub fn black_king_attacked(&self) -> bool {
+        self.king_attacked(Side::Black)
     }
 
     pub fn debug_validate_occupancies(&self) {
@@ -198,6 +268,7 @@ impl Node {
     // Does NOT update the total occupancy
     #[inline]
     pub fn capture_black(&mut self, sq_bb: BitBoard) {
+        self.hash_capture(Side::Black, sq_bb);
         let dst_mask = sq_bb.complement();
         self.occupancy_black.apply_mask(dst_mask);
         self.piece_mut(Piece::BlackPawn).apply_mask(dst_mask);
@@ -211,6 +282,7 @@ impl Node {
     // Does NOT update the total occupancy
     #[inline]
     pub fn capture_white(&mut self, sq_bb: BitBoard) {
+        self.hash_capture(Side::White, sq_bb);
         let dst_mask = sq_bb.complement();
         self.occupancy_white.apply_mask(dst_mask);
         self.piece_mut(Piece::WhitePawn).apply_mask(dst_mask);
@@ -229,6 +301,7 @@ impl Node {
 
         self.occupancy_total.apply(from);
         self.pieces[piece.to_index() as usize].apply_move(move_bb);
+        self.hash_squares(piece, move_bb);
 
         match piece.side() {
             Side::White => {
@@ -249,15 +322,186 @@ impl Node {
     #[inline]
     pub fn apply_move(&mut self, piece: Piece, move_bb: BitBoard) {
         self.pieces[piece.to_index() as usize].apply_move(move_bb);
+        self.hash_squares(piece, move_bb);
         self.occupancy_total.apply_move(move_bb);
         match piece.side() {
             Side::White => self.occupancy_white.apply_move(move_bb),
             Side::Black => self.occupancy_black.apply_move(move_bb),
         }
     }
+    // Toggles `piece` in the hashes on every square in `bb`
+    #[inline]
+    fn hash_squares(&mut self, piece: Piece, bb: BitBoard) {
+        for (sq, _) in bb {
+            self.hash_piece(piece, sq);
+        }
+    }
+    // Removes whichever of `side`'s pieces is on `sq_bb` from the hashes, if any
+    #[inline]
+    fn hash_capture(&mut self, side: Side, sq_bb: BitBoard) {
+        if let (Some(piece), Some(sq)) = (self.find_piece(side, sq_bb), sq_bb.get_square()) {
+            self.hash_piece(piece, sq);
+        }
+    }
+    #[inline]
+    fn hash_piece(&mut self, piece: Piece, sq: Square) {
+        let key = ZOBRIST_TABLE.hash_piece(piece, sq);
+        self.hash ^= key;
+        if let Piece::WhitePawn | Piece::BlackPawn = piece {
+            self.pawn_hash ^= key;
+        }
+    }
+    #[inline]
+    fn capture(&mut self, side: Side, sq_bb: BitBoard) {
+        match side {
+            Side::White => self.capture_white(sq_bb),
+            Side::Black => self.capture_black(sq_bb),
+        }
+    }
+
+    /// Moves the pieces on the board for `mv`, as played by the side to move, and returns the
+    /// piece that moved.
+    /// Only touches the piece and occupancy bitboards, the rest of the state is left as-is.
+    pub fn move_pieces(&mut self, mv: Move) -> Piece {
+        let side = self.side;
+        let from = mv.from().to_bitboard();
+        let to = mv.to().to_bitboard();
+        let move_bb = from.union(to);
+        let piece = self.find_piece(side, from);
+        debug_assert!(piece.is_some());
+        let piece = unsafe { piece.unwrap_unchecked() };
+
+        match mv.kind() {
+            MoveType::Quiet | MoveType::DoublePush => self.apply_move(piece, move_bb),
+            MoveType::Capture => self.apply_capture(piece, from, to),
+            MoveType::CaptureEnPassant => {
+                let victim = match side {
+                    Side::White => to.south(),
+                    Side::Black => to.north(),
+                };
+                self.capture(side.opponent(), victim);
+                self.occupancy_total.apply(victim);
+                self.apply_move(piece, move_bb);
+            }
+            MoveType::CastleKingside | MoveType::CastleQueenside => {
+                let (rook_from, rook_to) = castling_rook_squares(mv);
+                let rook = Piece::from_side_piece(side, PieceType::Rook);
+                self.apply_move(piece, move_bb);
+                self.apply_move(rook, rook_from.to_bitboard().union(rook_to.to_bitboard()));
+            }
+            kind => {
+                // Promotions
+                debug_assert!(kind.promotion().is_some());
+                let promotion = unsafe { kind.promotion().unwrap_unchecked() };
+                if kind.is_capture() {
+                    self.capture(side.opponent(), to);
+                    self.occupancy_total.apply(to);
+                }
+                self.toggle_piece(piece, from);
+                self.toggle_piece(Piece::from_side_piece(side, promotion), to);
+            }
+        }
+        piece
+    }
+
+    /// Puts a piece on an empty square, or removes it again
+    #[inline]
+    pub fn toggle_piece(&mut self, piece: Piece, sq_bb: BitBoard) {
+        self.piece_mut(piece).apply(sq_bb);
+        self.hash_squares(piece, sq_bb);
+        self.occupancy_total.apply(sq_bb);
+        match piece.side() {
+            Side::White => self.occupancy_white.apply(sq_bb),
+            Side::Black => self.occupancy_black.apply(sq_bb),
+        }
+    }
+
+    /// Inverse of [`Node::move_pieces`], with the side to move still set to the side that
+    /// played `mv`, and `captured` the piece that was returned by the capture, if any.
+    pub fn unmove_pieces(&mut self, mv: Move, captured: Option<Piece>) {
+        let side = self.side;
+        let from = mv.from().to_bitboard();
+        let to = mv.to().to_bitboard();
+        let move_bb = from.union(to);
+
+        match mv.kind() {
+            MoveType::CastleKingside | MoveType::CastleQueenside => {
+                let (rook_from, rook_to) = castling_rook_squares(mv);
+                let rook = Piece::from_side_piece(side, PieceType::Rook);
+                self.apply_move(Piece::from_side_piece(side, PieceType::King), move_bb);
+                self.apply_move(rook, rook_from.to_bitboard().union(rook_to.to_bitboard()));
+            }
+            MoveType::CaptureEnPassant => {
+                let victim = match side {
+                    Side::White => to.south(),
+                    Side::Black => to.north(),
+                };
+                self.apply_move(Piece::from_side_piece(side, PieceType::Pawn), move_bb);
+                self.toggle_piece(
+                    Piece::from_side_piece(side.opponent(), PieceType::Pawn),
+                    victim,
+                );
+            }
+            kind => {
+                match kind.promotion() {
+                    Some(promotion) => {
+                        self.toggle_piece(Piece::from_side_piece(side, promotion), to);
+                        self.toggle_piece(Piece::from_side_piece(side, PieceType::Pawn), from);
+                    }
+                    None => {
+                        let piece = self.find_piece(side, to);
+                        debug_assert!(piece.is_some());
+                        let piece = unsafe { piece.unwrap_unchecked() };
+                        self.apply_move(piece, move_bb);
+                    }
+                }
+                if let Some(captured) = captured {
+                    self.toggle_piece(captured, to);
+                }
+            }
+        }
+    }
+
     pub fn reset_en_passant(&mut self) {
+        if let Some(sq) = self.en_passant_square.to_square() {
+            self.hash ^= ZOBRIST_TABLE.hash_en_passant_square(sq.to_index() as usize);
+        }
         self.en_passant_square.reset();
     }
+    pub fn set_en_passant(&mut self, bb: BitBoard) {
+        self.reset_en_passant();
+        self.en_passant_square.set(bb);
+        if let Some(sq) = self.en_passant_square.to_square() {
+            self.hash ^= ZOBRIST_TABLE.hash_en_passant_square(sq.to_index() as usize);
+        }
+    }
+    /// See [`CastlingRights::revoke_for_move`].
+    pub fn revoke_castling_rights(&mut self, from: Square, to: Square) {
+        self.hash ^= ZOBRIST_TABLE.hash_castling_rights(&self.castling_rights);
+        self.castling_rights.revoke_for_move(from, to);
+        self.hash ^= ZOBRIST_TABLE.hash_castling_rights(&self.castling_rights);
+    }
+    pub fn flip_side(&mut self) {
+        self.hash ^= ZOBRIST_TABLE.hash_side(self.side);
+        self.side = self.side.opponent();
+        self.hash ^= ZOBRIST_TABLE.hash_side(self.side);
+    }
+    // Captures and pawn moves are irreversible, and reset the fifty-move counter
+    #[inline]
+    pub fn reset_halfmove_clock(&mut self) {
+        self.halfmove_clock = 0;
+    }
+}
+
+/// Source and destination of the rook in a castling move.
+pub const fn castling_rook_squares(mv: Move) -> (Square, Square) {
+    match (mv.kind(), mv.to()) {
+        (MoveType::CastleKingside, Square::G1) => (Square::H1, Square::F1),
+        (MoveType::CastleQueenside, Square::C1) => (Square::A1, Square::D1),
+        (MoveType::CastleKingside, Square::G8) => (Square::H8, Square::F8),
+        (MoveType::CastleQueenside, Square::C8) => (Square::A8, Square::D8),
+        _ => panic!("Not a castling move"),
+    }
 }
 
 impl Position {
@@ -275,3 +519,46 @@ impl Node {
     pub const POSITION_5: Node = Position::POSITION_5.to_node();
     pub const POSITION_6: Node = Position::POSITION_6.to_node();
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{bitboard::BitBoard, coord::Square, piece::Side, position::Position};
+
+    fn squares(squares: &[Square]) -> BitBoard {
+        BitBoard::from_squares(squares.iter().copied())
+    }
+
+    #[test]
+    fn attackers() {
+        let node = Position::parse_fen("3r4/8/2p1k3/8/2P5/4N3/B7/3QK3 w - - 0 1")
+            .unwrap()
+            .to_node();
+        let white = squares(&[Square::C4, Square::E3, Square::D1]);
+        let black = squares(&[Square::D8, Square::C6, Square::E6]);
+        assert_eq!(node.attackers_by(Side::White, Square::D5), white);
+        assert_eq!(node.attackers_by(Side::Black, Square::D5), black);
+        assert_eq!(
+            node.attackers_to(Square::D5, node.occupancy_total),
+            white.union(black)
+        );
+        // Without the pawn in the way, the bishop behind it joins in
+        let occupancy = node.occupancy_total.difference(Square::C4.to_bitboard());
+        assert!(
+            node.attackers_to(Square::D5, occupancy)
+                .contains(Square::A2)
+        );
+
+        assert!(node.square_is_attacked_by(Side::Black, Square::D7));
+        assert!(!node.square_is_attacked_by(Side::White, Square::H8));
+        assert!(!node.king_attacked(Side::White));
+
+        let check = Position::parse_fen("4k3/8/3N4/8/8/8/8/4K3 b - - 0 1")
+            .unwrap()
+            .to_node();
+        assert!(check.king_attacked(Side::Black));
+        assert_eq!(
+            check.attackers_by(Side::White, Square::E8),
+            Square::D6.to_bitboard()
+        );
+    }
+}
@@ -18,6 +18,7 @@ impl Node {
             let mut pos = self.clone();
             let bb_new = bb.lshift(8);
             pos.reset_en_passant();
+            pos.reset_halfmove_clock();
             pos.apply_move(Piece::WhitePawn, bb.union(bb_new));
             f(pos);
         }
@@ -32,7 +33,8 @@ impl Node {
         for (_, bb) in pawns {
             let mut pos = self.clone();
             let bb_new = bb.lshift(16);
-            pos.en_passant_square.set(bb.lshift(8));
+            pos.set_en_passant(bb.lshift(8));
+            pos.reset_halfmove_clock();
             pos.apply_move(Piece::WhitePawn, bb.union(bb_new));
             f(pos);
         }
@@ -48,7 +50,6 @@ impl Node {
             .difference(self.occupancy_total.rshift(8));
         for (_, bb) in pawns {
             let bb_new = bb.lshift(8);
-            let bb_move = bb.union(bb_new);
             // TODO look at asm difference between apply -> copy -> apply and copy -> apply -> apply
             // TODO orrrr we allow to mutate self, apply to self, and then afterwards undo
             for piece in [
@@ -59,10 +60,9 @@ impl Node {
             ] {
                 let mut pos = self.clone();
                 pos.reset_en_passant();
-                pos.piece_mut(Piece::WhitePawn).apply(bb);
-                pos.piece_mut(piece).apply(bb_new);
-                pos.occupancy_white.apply_move(bb_move);
-                pos.occupancy_total.apply_move(bb_move);
+                pos.reset_halfmove_clock();
+                pos.toggle_piece(Piece::WhitePawn, bb);
+                pos.toggle_piece(piece, bb_new);
                 f(pos);
             }
         }
@@ -76,6 +76,7 @@ impl Node {
             let mut pos = self.clone();
             let bb_new = bb.lshift(shift_bits);
             pos.reset_en_passant();
+            pos.reset_halfmove_clock();
             pos.apply_capture(Piece::WhitePawn, bb, bb_new);
             f(pos)
         }
@@ -92,7 +93,6 @@ impl Node {
         let attackers = self.white_pawns().intersect(victims.rshift(shift_bits));
         for (_, bb) in attackers {
             let bb_new = bb.lshift(shift_bits);
-            let bb_move = bb.union(bb_new);
             // TODO look at asm difference between apply -> copy -> apply and copy -> apply -> apply
             // TODO orrrr we allow to mutate self, apply to self, and then afterwards undo
             for piece in [
@@ -103,11 +103,11 @@ impl Node {
             ] {
                 let mut pos = self.clone();
                 pos.reset_en_passant();
+                pos.reset_halfmove_clock();
                 pos.capture_black(bb_new);
-                pos.piece_mut(Piece::WhitePawn).apply(bb);
-                pos.piece_mut(piece).apply(bb_new);
-                pos.occupancy_white.apply_move(bb_move);
-                pos.occupancy_total.apply(bb);
+                pos.occupancy_total.apply(bb_new);
+                pos.toggle_piece(Piece::WhitePawn, bb);
+                pos.toggle_piece(piece, bb_new);
                 f(pos)
             }
         }
@@ -129,11 +129,9 @@ impl Node {
     fn apply_white_en_passant(&mut self, bb_from: BitBoard, bb_to: BitBoard, bb_victim: BitBoard) {
         let bb_move = bb_from.union(bb_to);
         self.reset_en_passant();
-        self.piece_mut(Piece::WhitePawn).apply_move(bb_move);
-        self.piece_mut(Piece::BlackPawn).apply(bb_victim);
-        self.occupancy_white.apply_move(bb_move);
-        self.occupancy_black.apply(bb_victim);
-        self.occupancy_total.apply(bb_move.union(bb_victim));
+        self.reset_halfmove_clock();
+        self.apply_move(Piece::WhitePawn, bb_move);
+        self.toggle_piece(Piece::BlackPawn, bb_victim);
     }
 
     pub fn for_white_en_passant_east<F: FnMut(Node)>(&self, mut f: F) {
@@ -180,6 +178,7 @@ mod tests {
         let mut i = 0;
         node.for_white_simple_push(|pos| {
             pos.debug_validate_occupancies();
+            pos.debug_validate_hash();
             i += 1
         });
         i
@@ -188,6 +187,7 @@ mod tests {
         let mut i = 0;
         node.for_white_double_push(|pos| {
             pos.debug_validate_occupancies();
+            pos.debug_validate_hash();
             i += 1
         });
         i
@@ -196,6 +196,7 @@ mod tests {
         let mut i = 0;
         node.for_white_promotion_push(|pos| {
             pos.debug_validate_occupancies();
+            pos.debug_validate_hash();
             i += 1
         });
         i
@@ -204,6 +205,7 @@ mod tests {
         let mut i = 0;
         node.for_white_east_simple_attack(|pos| {
             pos.debug_validate_occupancies();
+            pos.debug_validate_hash();
             i += 1
         });
         i
@@ -212,6 +214,7 @@ mod tests {
         let mut i = 0;
         node.for_white_west_simple_attack(|pos| {
             pos.debug_validate_occupancies();
+            pos.debug_validate_hash();
             i += 1
         });
         i
@@ -220,6 +223,7 @@ mod tests {
         let mut i = 0;
         node.for_white_east_promotion_attack(|pos| {
             pos.debug_validate_occupancies();
+            pos.debug_validate_hash();
             i += 1
         });
         i
@@ -228,6 +232,7 @@ mod tests {
         let mut i = 0;
         node.for_white_west_promotion_attack(|pos| {
             pos.debug_validate_occupancies();
+            pos.debug_validate_hash();
             i += 1
         });
         i
@@ -236,6 +241,7 @@ mod tests {
         let mut i = 0;
         node.for_white_en_passant_east(|pos| {
             pos.debug_validate_occupancies();
+            pos.debug_validate_hash();
             i += 1
         });
         i
@@ -244,6 +250,7 @@ mod tests {
         let mut i = 0;
         node.for_white_en_passant_west(|pos| {
             pos.debug_validate_occupancies();
+            pos.debug_validate_hash();
             i += 1
         });
         i
@@ -255,8 +262,6 @@ mod tests {
         const PAWN_PLAYGROUND: Node =
             Position::from_fen("k4n1n/2P1P1P1/3b4/4Pp2/8/8/2P5/K7 w - f6 0 1").to_node();
 
-        const POS: Position = Position::from_fen("k4n1n/2P1P1P1/3b4/4Pp2/8/8/2P5/K7 w - f6 0 1");
-        // assert_ne!(POS, POS);
         assert_eq!(count_white_simple_pushes(PAWN_PLAYGROUND), 2);
         assert_eq!(count_white_double_pushes(PAWN_PLAYGROUND), 1);
         assert_eq!(count_white_promo_pushes(PAWN_PLAYGROUND), 12);
@@ -268,6 +273,16 @@ mod tests {
         assert_eq!(count_white_west_en_passant(PAWN_PLAYGROUND), 0);
     }
 
+    #[test]
+    fn pawn_moves_reset_halfmove_clock() {
+        let mut clocks = Vec::new();
+        Node::POSITION_5.for_white_simple_push(|pos| clocks.push(pos.halfmove_clock));
+        Node::POSITION_5.for_white_double_push(|pos| clocks.push(pos.halfmove_clock));
+        assert!(!clocks.is_empty());
+        assert!(clocks.into_iter().all(|clock| clock == 0));
+        assert_eq!(Node::POSITION_5.halfmove_clock, 1);
+    }
+
     #[test]
     fn white_simple_push() {
         assert_eq!(count_white_simple_pushes(Node::POSITION_1), 8);
@@ -0,0 +1,199 @@
+use crate::{
+    bitboard::BitBoard,
+    moves::{Move, MoveType},
+    piece::{Piece, PieceType, Side},
+    pieces::{bishop::bishop_moves, rook::rook_moves},
+};
+
+use super::node::Node;
+
+// Cheapest first, since that's what a sensible player recaptures with
+const RECAPTURE_ORDER: [PieceType; 6] = [
+    PieceType::Pawn,
+    PieceType::Knight,
+    PieceType::Bishop,
+    PieceType::Rook,
+    PieceType::Queen,
+    PieceType::King,
+];
+
+// More than enough, there are at most 32 pieces on the board
+const MAX_EXCHANGES: usize = 32;
+
+impl Node {
+    /// Static exchange evaluation: the material the side to move wins or loses with `mv`, if both
+    /// sides keep recapturing on the target square with their least valuable piece for as long
+    /// as it pays off. Pins are ignored. `mv` doesn't have to be a capture, a quiet move to an
+    /// attacked square scores the loss of the moved piece.
+    pub fn see(&self, mv: Move) -> i32 {
+        if mv.is_castle() {
+            return 0;
+        }
+        let (from, to) = (mv.from(), mv.to());
+        let mut occupancy = self.occupancy_total.difference(from.to_bitboard());
+
+        // gains[i] is what the side making the i-th capture gains, assuming it's the last one
+        let mut gains = [0; MAX_EXCHANGES];
+        gains[0] = match mv.kind() {
+            MoveType::CaptureEnPassant => {
+                let victim = match self.side {
+                    Side::White => to.to_bitboard().south(),
+                    Side::Black => to.to_bitboard().north(),
+                };
+                occupancy = occupancy.difference(victim);
+                PieceType::Pawn.value()
+            }
+            kind if kind.is_capture() => self
+                .find_piece(self.side.opponent(), to.to_bitboard())
+                .map_or(0, |piece| piece.piece_type().value()),
+            _ => 0,
+        };
+        // The piece standing on the target square, which the next capture wins
+        let mut on_square = match mv.promotion() {
+            Some(promotion) => {
+                gains[0] += promotion.value() - PieceType::Pawn.value();
+                promotion
+            }
+            None => self
+                .find_piece(self.side, from.to_bitboard())
+                .map_or(PieceType::Pawn, Piece::piece_type),
+        };
+
+        let diagonal = self
+            .pieces_of_type(PieceType::Bishop)
+            .union(self.pieces_of_type(PieceType::Queen));
+        let orthogonal = self
+            .pieces_of_type(PieceType::Rook)
+            .union(self.pieces_of_type(PieceType::Queen));
+        let mut attackers = self.attackers_to(to, occupancy).intersect(occupancy);
+        let mut side = self.side.opponent();
+        let mut depth = 0;
+        loop {
+            let ours = attackers.intersect(self.occupancy(side));
+            let Some((piece_type, attacker)) = RECAPTURE_ORDER.iter().find_map(|&piece_type| {
+                let candidates =
+                    ours.intersect(self.piece(Piece::from_side_piece(side, piece_type)));
+                let bits = candidates.to_bits();
+                (bits != 0).then(|| (piece_type, BitBoard::from_bits(bits & bits.wrapping_neg())))
+            }) else {
+                break;
+            };
+            // The king can only take if that doesn't walk into a recapture
+            if piece_type == PieceType::King
+                && attackers
+                    .difference(ours)
+                    .intersects(self.occupancy(side.opponent()))
+            {
+                break;
+            }
+
+            depth += 1;
+            gains[depth] = on_square.value() - gains[depth - 1];
+            on_square = piece_type;
+            occupancy = occupancy.difference(attacker);
+            // Removing the attacker may have uncovered a slider behind it
+            attackers = attackers
+                .union(bishop_moves(to, occupancy).intersect(diagonal))
+                .union(rook_moves(to, occupancy).intersect(orthogonal))
+                .intersect(occupancy);
+            side = side.opponent();
+            if depth + 1 == MAX_EXCHANGES {
+                break;
+            }
+        }
+
+        // Walk back, letting each side stop capturing when continuing would lose material
+        while depth > 0 {
+            gains[depth - 1] = -(-gains[depth - 1]).max(gains[depth]);
+            depth -= 1;
+        }
+        gains[0]
+    }
+
+    /// Whether [`Node::see`] of `mv` is at least `threshold`.
+    pub fn see_ge(&self, mv: Move, threshold: i32) -> bool {
+        self.see(mv) >= threshold
+    }
+
+    fn pieces_of_type(&self, piece_type: PieceType) -> BitBoard {
+        self.piece(Piece::from_side_piece(Side::White, piece_type))
+            .union(self.piece(Piece::from_side_piece(Side::Black, piece_type)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{position::Position, uci::parse_uci_move};
+
+    fn see(fen: &str, mv: &str) -> i32 {
+        let node = Position::parse_fen(fen).unwrap().to_node();
+        let mv = parse_uci_move(&node, mv).unwrap();
+        node.see(mv)
+    }
+
+    #[test]
+    fn exchanges() {
+        // Undefended pawn
+        assert_eq!(
+            see("1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1", "e1e5"),
+            100
+        );
+        // Knight for a pawn, the long exchange doesn't change that
+        assert_eq!(
+            see(
+                "1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1",
+                "d3e5"
+            ),
+            100 - 320
+        );
+        // Defended pawn
+        assert_eq!(see("4k3/8/2p5/3p4/8/8/8/3QK3 w - - 0 1", "d1d5"), 100 - 900);
+        // Equal trade
+        assert_eq!(see("4k3/8/2p5/3n4/8/4N3/8/4K3 w - - 0 1", "e3d5"), 0);
+        // Defended by the king only, which can't take because of the second attacker
+        assert_eq!(see("8/8/4k3/3p4/8/8/3R4/3R2K1 w - - 0 1", "d2d5"), 100);
+        assert_eq!(see("8/8/4k3/3p4/8/8/8/3R2K1 w - - 0 1", "d1d5"), 100 - 500);
+    }
+
+    #[test]
+    fn x_rays() {
+        // The rook behind the rook joins in
+        assert_eq!(see("3rk3/8/8/3p4/8/8/3R4/3R2K1 w - - 0 1", "d2d5"), 100);
+        // And the queen behind the bishop
+        assert_eq!(see("4k3/8/5b2/4p3/8/8/1B6/Q3K3 w - - 0 1", "b2e5"), 100);
+        // Unless the defender has one too
+        assert_eq!(
+            see("4k3/6q1/5b2/4p3/8/2B5/1Q6/4K3 w - - 0 1", "c3e5"),
+            100 - 330
+        );
+    }
+
+    #[test]
+    fn special_moves() {
+        // En passant takes a pawn that isn't on the target square, uncovering the rook behind it
+        assert_eq!(see("3rk3/8/8/3pP3/8/8/8/3RK3 w - d6 0 1", "e5d6"), 100);
+        assert_eq!(see("4k3/8/8/2bpP3/8/8/8/4K3 w - d6 0 1", "e5d6"), 0);
+        // Promotions
+        assert_eq!(see("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1", "b7b8q"), 800);
+        assert_eq!(see("r3k3/1P6/8/8/8/8/8/4K3 w - - 0 1", "b7b8q"), 800 - 900);
+        assert_eq!(
+            see("r3k3/1Pn5/8/8/8/8/8/4K3 w - - 0 1", "b7a8q"),
+            500 + 800 - 900
+        );
+        // Quiet moves to attacked squares
+        assert_eq!(see("4k3/8/4p3/8/8/8/8/3QK3 w - - 0 1", "d1d5"), -900);
+        assert_eq!(see("4k3/8/4p3/8/8/8/8/3QK3 w - - 0 1", "d1d4"), 0);
+        assert_eq!(see("4k3/8/8/8/8/8/8/R3K2R w KQ - 0 1", "e1g1"), 0);
+    }
+
+    #[test]
+    fn thresholds() {
+        let node = Position::parse_fen("4k3/8/2p5/3n4/8/4N3/8/4K3 w - - 0 1")
+            .unwrap()
+            .to_node();
+        let mv = parse_uci_move(&node, "e3d5").unwrap();
+        assert!(node.see_ge(mv, 0));
+        assert!(node.see_ge(mv, -100));
+        assert!(!node.see_ge(mv, 1));
+    }
+}
@@ -0,0 +1,212 @@
+use std::{fmt, ops::AddAssign};
+
+use crate::moves::{Move, MoveType};
+
+use super::node::{Node, castling_rook_squares};
+
+/// Leaf counts broken down by the categories of the published perft tables. Everything but
+/// `nodes` describes the last move leading to the leaf, or the leaf position itself.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct PerftStats {
+    pub nodes: u64,
+    /// Including en passant captures
+    pub captures: u64,
+    pub en_passant: u64,
+    pub castles: u64,
+    pub promotions: u64,
+    pub checks: u64,
+    /// Checks given only by a piece other than the one that moved, double checks aren't counted
+    pub discovered_checks: u64,
+    pub double_checks: u64,
+    pub checkmates: u64,
+}
+
+impl AddAssign for PerftStats {
+    fn add_assign(&mut self, rhs: PerftStats) {
+        self.nodes += rhs.nodes;
+        self.captures += rhs.captures;
+        self.en_passant += rhs.en_passant;
+        self.castles += rhs.castles;
+        self.promotions += rhs.promotions;
+        self.checks += rhs.checks;
+        self.discovered_checks += rhs.discovered_checks;
+        self.double_checks += rhs.double_checks;
+        self.checkmates += rhs.checkmates;
+    }
+}
+
+impl fmt::Display for PerftStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rows = [
+            ("Nodes", self.nodes),
+            ("Captures", self.captures),
+            ("En passant", self.en_passant),
+            ("Castles", self.castles),
+            ("Promotions", self.promotions),
+            ("Checks", self.checks),
+            ("Discovered checks", self.discovered_checks),
+            ("Double checks", self.double_checks),
+            ("Checkmates", self.checkmates),
+        ];
+        for (index, (name, count)) in rows.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{:<18}{:>14}", name, count)?;
+        }
+        Ok(())
+    }
+}
+
+impl PerftStats {
+    // `node` is the leaf, after playing `mv`
+    fn record(&mut self, node: &Node, mv: Move) {
+        self.nodes += 1;
+        self.captures += mv.is_capture() as u64;
+        self.en_passant += (mv.kind() == MoveType::CaptureEnPassant) as u64;
+        self.castles += mv.is_castle() as u64;
+        self.promotions += mv.promotion().is_some() as u64;
+
+        let checkers = node.attackers_by(node.side.opponent(), node.king_square(node.side));
+        if checkers.is_empty() {
+            return;
+        }
+        self.checks += 1;
+        let moved = if mv.is_castle() {
+            mv.to().to_bitboard().set(castling_rook_squares(mv).1)
+        } else {
+            mv.to().to_bitboard()
+        };
+        // The published tables count a double check only as a double check, even though one of
+        // the checkers is always discovered
+        let double_check = checkers.popcount() > 1;
+        self.discovered_checks +=
+            (!double_check && checkers.difference(moved).is_nonempty()) as u64;
+        self.double_checks += double_check as u64;
+        self.checkmates += node.legal_moves().is_empty() as u64;
+    }
+}
+
+impl Node {
+    /// [`Node::perft`], with the leaves sorted into the categories of [`PerftStats`]. Comparing
+    /// these against the published tables narrows a wrong count down to the kind of move that
+    /// causes it.
+    pub fn perft_stats(&self, depth: u8) -> PerftStats {
+        let mut stats = PerftStats::default();
+        if depth == 0 {
+            stats.nodes = 1;
+        } else {
+            self.clone().collect_stats(depth, &mut stats);
+        }
+        stats
+    }
+
+    fn collect_stats(&mut self, depth: u8, stats: &mut PerftStats) {
+        for &mv in &self.legal_moves() {
+            let undo = self.make(mv);
+            if depth == 1 {
+                stats.record(self, mv);
+            } else {
+                self.collect_stats(depth - 1, stats);
+            }
+            self.unmake(mv, undo);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::perft::{node::Node, stats::PerftStats};
+
+    // Nodes, captures, en passant, castles, promotions, checks, discovered checks, double checks
+    // and checkmates, as published on the Chess Programming Wiki
+    fn stats(counts: [u64; 9]) -> PerftStats {
+        let [
+            nodes,
+            captures,
+            en_passant,
+            castles,
+            promotions,
+            checks,
+            discovered_checks,
+            double_checks,
+            checkmates,
+        ] = counts;
+        PerftStats {
+            nodes,
+            captures,
+            en_passant,
+            castles,
+            promotions,
+            checks,
+            discovered_checks,
+            double_checks,
+            checkmates,
+        }
+    }
+
+    #[test]
+    fn published_tables() {
+        let start = Node::POSITION_1;
+        assert_eq!(start.perft_stats(0), stats([1, 0, 0, 0, 0, 0, 0, 0, 0]));
+        assert_eq!(start.perft_stats(1), stats([20, 0, 0, 0, 0, 0, 0, 0, 0]));
+        assert_eq!(
+            start.perft_stats(3),
+            stats([8902, 34, 0, 0, 0, 12, 0, 0, 0])
+        );
+        assert_eq!(
+            start.perft_stats(4),
+            stats([197_281, 1576, 0, 0, 0, 469, 0, 0, 8])
+        );
+
+        let kiwipete = Node::POSITION_2;
+        assert_eq!(kiwipete.perft_stats(1), stats([48, 8, 0, 2, 0, 0, 0, 0, 0]));
+        assert_eq!(
+            kiwipete.perft_stats(2),
+            stats([2039, 351, 1, 91, 0, 3, 0, 0, 0])
+        );
+        assert_eq!(
+            kiwipete.perft_stats(3),
+            stats([97_862, 17_102, 45, 3162, 0, 993, 0, 0, 1])
+        );
+        // The double checks aren't discovered checks as well
+        assert_eq!(
+            kiwipete.perft_stats(4),
+            stats([4_085_603, 757_163, 1929, 128_013, 15_172, 25_523, 42, 6, 43])
+        );
+
+        let endgame = Node::POSITION_3;
+        assert_eq!(
+            endgame.perft_stats(3),
+            stats([2812, 209, 2, 0, 0, 267, 3, 0, 0])
+        );
+        assert_eq!(
+            endgame.perft_stats(4),
+            stats([43_238, 3348, 123, 0, 0, 1680, 106, 0, 17])
+        );
+        assert_eq!(
+            endgame.perft_stats(5),
+            stats([674_624, 52_051, 1165, 0, 0, 52_950, 1292, 3, 0])
+        );
+
+        let promotions = Node::POSITION_4;
+        assert_eq!(
+            promotions.perft_stats(2),
+            stats([264, 87, 0, 6, 48, 10, 0, 0, 0])
+        );
+        assert_eq!(
+            promotions.perft_stats(3),
+            stats([9467, 1021, 4, 0, 120, 38, 2, 0, 22])
+        );
+    }
+
+    #[test]
+    fn sums() {
+        let mut sum = PerftStats::default();
+        sum += Node::POSITION_2.perft_stats(2);
+        sum += Node::POSITION_2.perft_stats(2);
+        assert_eq!(sum.nodes, 2 * 2039);
+        assert_eq!(sum.castles, 2 * 91);
+        assert!(sum.to_string().contains("Discovered checks"));
+    }
+}
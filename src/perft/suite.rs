@@ -0,0 +1,210 @@
+use std::fmt;
+
+use crate::position::{FenError, Position};
+
+use super::node::Node;
+
+/// One position of a perft suite in the `perftsuite.epd` format:
+/// `<FEN> ;D1 <count> ;D2 <count> ...`, where any subset of depths may be listed.
+#[derive(Clone, Debug)]
+pub struct SuiteEntry {
+    /// 1-based line number in the suite file
+    pub line: usize,
+    pub fen: String,
+    pub node: Node,
+    /// Expected leaf counts by depth, sorted by depth
+    pub counts: Vec<(u8, u64)>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SuiteError {
+    BadFen {
+        line: usize,
+        error: FenError,
+    },
+    /// A field after the FEN that isn't `D<depth> <count>`
+    BadCount {
+        line: usize,
+        field: String,
+    },
+}
+
+impl fmt::Display for SuiteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SuiteError::BadFen { line, error } => {
+                write!(f, "line {}: invalid FEN: {}", line, error)
+            }
+            SuiteError::BadCount { line, field } => {
+                write!(
+                    f,
+                    "line {}: expected D<depth> <count>, got {:?}",
+                    line, field
+                )
+            }
+        }
+    }
+}
+
+/// A position whose perft count differs from the suite at `depth`, the shallowest depth at which
+/// it does. Deeper counts are then wrong too, so they aren't checked.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Mismatch {
+    pub line: usize,
+    pub fen: String,
+    pub depth: u8,
+    pub expected: u64,
+    pub found: u64,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}: {} at depth {}: expected {}, found {}",
+            self.line, self.fen, self.depth, self.expected, self.found
+        )
+    }
+}
+
+/// Parses a suite, skipping empty lines and lines starting with `#`.
+pub fn parse_suite(text: &str) -> Result<Vec<SuiteEntry>, SuiteError> {
+    let mut entries = Vec::new();
+    for (index, text) in text.lines().enumerate() {
+        let line = index + 1;
+        let text = text.trim();
+        if text.is_empty() || text.starts_with('#') {
+            continue;
+        }
+        let mut fields = text.split(';');
+        let fen = fields.next().unwrap_or_default().trim();
+        let node = Position::parse_fen(fen)
+            .map_err(|error| SuiteError::BadFen { line, error })?
+            .to_node();
+        let mut counts = fields
+            .map(|field| {
+                let bad_count = || SuiteError::BadCount {
+                    line,
+                    field: field.to_string(),
+                };
+                let (depth, count) = field.trim().split_once(' ').ok_or_else(bad_count)?;
+                let depth = depth.strip_prefix('D').ok_or_else(bad_count)?;
+                Ok((
+                    depth.parse().map_err(|_| bad_count())?,
+                    count.trim().parse().map_err(|_| bad_count())?,
+                ))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        counts.sort_unstable();
+        entries.push(SuiteEntry {
+            line,
+            fen: fen.to_string(),
+            node,
+            counts,
+        });
+    }
+    Ok(entries)
+}
+
+impl SuiteEntry {
+    /// Counts the listed depths up to `max_depth` with `perft`, shallowest first, and returns the
+    /// first one that's wrong.
+    pub fn check<F: Fn(&Node, u8) -> u64>(&self, max_depth: u8, perft: F) -> Option<Mismatch> {
+        self.counts
+            .iter()
+            .take_while(|&&(depth, _)| depth <= max_depth)
+            .find_map(|&(depth, expected)| {
+                let found = perft(&self.node, depth);
+                (found != expected).then(|| Mismatch {
+                    line: self.line,
+                    fen: self.fen.clone(),
+                    depth,
+                    expected,
+                    found,
+                })
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::perft::{
+        cache::PerftCache,
+        node::Node,
+        suite::{Mismatch, SuiteError, parse_suite},
+    };
+
+    const SUITE: &str = include_str!("../../tests/data/perftsuite.epd");
+
+    // Deep enough to get through the whole suite except the largest counts, which take too long
+    // without optimizations, so positions with only large counts are skipped. Set
+    // PERFT_SUITE_DEPTH to go deeper.
+    const DEFAULT_DEPTH: u8 = 6;
+    const MAX_DEFAULT_NODES: u64 = 1_500_000;
+
+    #[test]
+    fn parse() {
+        let entries =
+            parse_suite("# comment\n\n4k3/8/8/8/8/8/8/4K3 w - - 0 1 ;D2 25 ;D1 5\n").unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].line, 3);
+        assert_eq!(entries[0].fen, "4k3/8/8/8/8/8/8/4K3 w - - 0 1");
+        assert_eq!(entries[0].counts, vec![(1, 5), (2, 25)]);
+
+        assert!(matches!(
+            parse_suite("4k3/8/8/8/8/8/8/4K3 w - - 0 1 ;D1"),
+            Err(SuiteError::BadCount { line: 1, .. })
+        ));
+        assert!(matches!(
+            parse_suite("4k3/8/8/8/8/8/8/4K3 w - - 0 1 ;X1 5"),
+            Err(SuiteError::BadCount { line: 1, .. })
+        ));
+        assert!(matches!(
+            parse_suite("\n4k3/8/8 w - - 0 1 ;D1 5"),
+            Err(SuiteError::BadFen { line: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn first_mismatch() {
+        let entries = parse_suite("4k3/8/8/8/8/8/8/4K3 w - - 0 1 ;D1 5 ;D2 26 ;D3 1").unwrap();
+        let mismatch = entries[0].check(3, Node::perft);
+        assert_eq!(
+            mismatch,
+            Some(Mismatch {
+                line: 1,
+                fen: "4k3/8/8/8/8/8/8/4K3 w - - 0 1".to_string(),
+                depth: 2,
+                expected: 26,
+                found: 25,
+            })
+        );
+        assert_eq!(entries[0].check(1, Node::perft), None);
+    }
+
+    #[test]
+    fn suite() {
+        let max_depth = std::env::var("PERFT_SUITE_DEPTH")
+            .map(|depth| depth.parse().expect("PERFT_SUITE_DEPTH"))
+            .ok();
+        let cache = PerftCache::new(16);
+        let threads = num_cpus::get();
+        let mismatches: Vec<String> = parse_suite(SUITE)
+            .unwrap()
+            .iter()
+            .filter_map(|entry| {
+                let mut entry = entry.clone();
+                if max_depth.is_none() {
+                    entry
+                        .counts
+                        .retain(|&(_, count)| count <= MAX_DEFAULT_NODES);
+                }
+                entry.check(max_depth.unwrap_or(DEFAULT_DEPTH), |node, depth| {
+                    node.perft_parallel_hashed(depth, threads, &cache)
+                })
+            })
+            .map(|mismatch| mismatch.to_string())
+            .collect();
+        assert!(mismatches.is_empty(), "{}", mismatches.join("\n"));
+    }
+}
@@ -0,0 +1,271 @@
+use crate::{
+    moves::Move,
+    perft::node::Node,
+    position::{FenError, Position},
+    san::{SanError, parse_san},
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GameResult {
+    WhiteWins,
+    BlackWins,
+    Draw,
+    /// `*`, or no result given
+    Unknown,
+}
+
+impl GameResult {
+    pub fn from_token(token: &str) -> Option<Self> {
+        match token {
+            "1-0" => Some(GameResult::WhiteWins),
+            "0-1" => Some(GameResult::BlackWins),
+            "1/2-1/2" => Some(GameResult::Draw),
+            "*" => Some(GameResult::Unknown),
+            _ => None,
+        }
+    }
+}
+
+/// A single game from a PGN file. Only the main line is kept, comments, variations and numeric
+/// annotation glyphs are dropped.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    /// The main line, in SAN
+    pub moves: Vec<String>,
+    pub result: GameResult,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum PgnError {
+    BadFen(FenError),
+    BadMove {
+        ply: usize,
+        san: String,
+        error: SanError,
+    },
+}
+
+impl std::fmt::Display for PgnError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PgnError::BadFen(err) => write!(f, "invalid FEN tag: {}", err),
+            PgnError::BadMove { ply, san, error } => {
+                write!(f, "{} at ply {}: {:?}", error, ply + 1, san)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PgnError {}
+
+impl PgnGame {
+    fn empty() -> Self {
+        PgnGame {
+            tags: Vec::new(),
+            moves: Vec::new(),
+            result: GameResult::Unknown,
+        }
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The `FEN` tag if present, and the standard starting position otherwise.
+    pub fn start_position(&self) -> Result<Position, PgnError> {
+        match self.tag("FEN") {
+            Some(fen) => Position::parse_fen(fen).map_err(PgnError::BadFen),
+            None => Ok(Position::START_POS),
+        }
+    }
+
+    /// Replays the main line, calling `f` with every position and the move played in it.
+    /// Stops at the first move that doesn't parse, returning the error.
+    pub fn replay<F: FnMut(&Node, Move)>(&self, mut f: F) -> Result<(), PgnError> {
+        let mut node = self.start_position()?.to_node();
+        for (ply, san) in self.moves.iter().enumerate() {
+            let mv = parse_san(&node, san).map_err(|error| PgnError::BadMove {
+                ply,
+                san: san.clone(),
+                error,
+            })?;
+            f(&node, mv);
+            node.make(mv);
+        }
+        Ok(())
+    }
+}
+
+/// Splits PGN text into games. Parsing is lenient: anything that isn't a tag pair, move number,
+/// result, comment, variation, or annotation is taken to be a SAN move, to be validated on replay.
+pub fn parse_pgn(text: &str) -> Vec<PgnGame> {
+    let mut games = Vec::new();
+    let mut game = PgnGame::empty();
+    let mut variation_depth = 0usize;
+    let mut chars = text.char_indices().peekable();
+
+    // Whether the current game has any content yet
+    let started = |game: &PgnGame| !game.tags.is_empty() || !game.moves.is_empty();
+
+    while let Some((start, c)) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '{' => {
+                for (_, c) in chars.by_ref() {
+                    if c == '}' {
+                        break;
+                    }
+                }
+            }
+            ';' => {
+                for (_, c) in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            '(' => variation_depth += 1,
+            ')' => variation_depth = variation_depth.saturating_sub(1),
+            '[' if variation_depth == 0 => {
+                // A tag after moves means we missed the result of the previous game
+                if !game.moves.is_empty() {
+                    games.push(std::mem::replace(&mut game, PgnGame::empty()));
+                }
+                let mut end = text.len();
+                for (i, c) in chars.by_ref() {
+                    if c == ']' {
+                        end = i;
+                        break;
+                    }
+                }
+                if let Some(tag) = parse_tag(&text[start + 1..end]) {
+                    game.tags.push(tag);
+                }
+            }
+            _ => {
+                let mut end = text.len();
+                while let Some(&(i, c)) = chars.peek() {
+                    if c.is_whitespace() || "{}();[".contains(c) {
+                        end = i;
+                        break;
+                    }
+                    chars.next();
+                }
+                let token = &text[start..end];
+                if variation_depth > 0 || token.starts_with('$') {
+                    continue;
+                }
+                if let Some(result) = GameResult::from_token(token) {
+                    game.result = result;
+                    if started(&game) {
+                        games.push(std::mem::replace(&mut game, PgnGame::empty()));
+                    }
+                    continue;
+                }
+                // Move numbers, possibly glued to the move as in `1.e4`
+                let token = token.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+                if !token.is_empty() {
+                    game.moves.push(token.to_string());
+                }
+            }
+        }
+    }
+    if started(&game) {
+        if game.result == GameResult::Unknown
+            && let Some(result) = game.tag("Result").and_then(GameResult::from_token)
+        {
+            game.result = result;
+        }
+        games.push(game);
+    }
+    games
+}
+
+// `Name "Value"`, with `\"` and `\\` escapes in the value
+fn parse_tag(tag: &str) -> Option<(String, String)> {
+    let (name, value) = tag.trim().split_once(char::is_whitespace)?;
+    let value = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unescaped.extend(chars.next()),
+            c => unescaped.push(c),
+        }
+    }
+    Some((name.to_string(), unescaped))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        pgn::{GameResult, PgnError, parse_pgn},
+        san::SanError,
+    };
+
+    const PGN: &str = r#"
+[Event "Casual game"]
+[White "Morphy, \"Paul\""]
+[Black "Duke of Brunswick and Count Isouard"]
+[Result "1-0"]
+
+1. e4 e5 2. Nf3 d6 3. d4 Bg4 {This is a weak move already.} 4. dxe5 Bxf3 5. Qxf3 dxe5
+6. Bc4 Nf6 7. Qb3 $1 Qe7 8. Nc3 (8. Qxb7 Qb4+) 8... c6 9. Bg5 b5 10. Nxb5 cxb5 11. Bxb5+
+Nbd7 12. O-O-O Rd8 13. Rxd7 Rxd7 14. Rd1 Qe6 15. Bxd7+ Nxd7 16. Qb8+ Nxb8 17. Rd8# 1-0
+
+[Event "Scholar's mate"]
+[Result "0-1"]
+[FEN "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR b KQkq - 0 1"]
+
+1... e5 2.Bc4 Nc6 ; line comment
+3.Qh5 Nf6 4.Qxf7# *
+
+1. e4 e5 2. Ke3 1/2-1/2
+"#;
+
+    #[test]
+    fn games() {
+        let games = parse_pgn(PGN);
+        assert_eq!(games.len(), 3);
+
+        let morphy = &games[0];
+        assert_eq!(morphy.tag("White"), Some("Morphy, \"Paul\""));
+        assert_eq!(morphy.tag("Round"), None);
+        assert_eq!(morphy.result, GameResult::WhiteWins);
+        assert_eq!(morphy.moves.len(), 33);
+        assert_eq!(morphy.moves[14], "Nc3");
+        assert_eq!(morphy.moves[15], "c6");
+        let mut plies = 0;
+        morphy.replay(|_, _| plies += 1).unwrap();
+        assert_eq!(plies, 33);
+
+        // Black to move first, and the result token wins over the tag
+        let scholar = &games[1];
+        assert_eq!(scholar.result, GameResult::Unknown);
+        assert_eq!(scholar.moves, ["e5", "Bc4", "Nc6", "Qh5", "Nf6", "Qxf7#"]);
+        assert!(matches!(
+            scholar.replay(|_, _| {}),
+            Err(PgnError::BadMove {
+                ply: 1,
+                error: SanError::Illegal,
+                ..
+            })
+        ));
+
+        let illegal = &games[2];
+        assert!(illegal.tags.is_empty());
+        assert_eq!(illegal.result, GameResult::Draw);
+        assert!(matches!(
+            illegal.replay(|_, _| {}),
+            Err(PgnError::BadMove {
+                ply: 2,
+                error: SanError::Illegal,
+                ..
+            })
+        ));
+    }
+}
@@ -1,5 +1,3 @@
-use crate::{coord::Square, zobrist_table};
-
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum Piece {
     WhitePawn = 0,
@@ -199,18 +197,21 @@ impl PieceType {
         }
     }
     pub const fn is_slider(self) -> bool {
-        match self {
-            PieceType::Bishop => true,
-            PieceType::Rook => true,
-            PieceType::Queen => true,
-            _ => false,
-        }
+        matches!(self, PieceType::Bishop | PieceType::Rook | PieceType::Queen)
     }
     pub const fn is_jumper(self) -> bool {
+        matches!(self, PieceType::Knight | PieceType::King)
+    }
+    /// Nominal value in centipawns, for move ordering and exchange evaluation.
+    /// The king can't be traded, so it's worth nothing here.
+    pub const fn value(self) -> i32 {
         match self {
-            PieceType::Knight => true,
-            PieceType::King => true,
-            _ => false,
+            PieceType::Pawn => 100,
+            PieceType::Knight => 320,
+            PieceType::Bishop => 330,
+            PieceType::Rook => 500,
+            PieceType::Queen => 900,
+            PieceType::King => 0,
         }
     }
 }
@@ -0,0 +1,132 @@
+use crate::{bitboard::BitBoard, coord::Square};
+
+/// The squares strictly between `a` and `b` if they share a rank, file, or diagonal, and the empty
+/// set otherwise.
+pub fn between(a: Square, b: Square) -> BitBoard {
+    unsafe {
+        *BETWEEN
+            .get_unchecked(a.to_index() as usize)
+            .get_unchecked(b.to_index() as usize)
+    }
+}
+
+/// The entire rank, file, or diagonal through both `a` and `b`, from edge to edge, if they are
+/// aligned, and the empty set otherwise.
+pub fn line(a: Square, b: Square) -> BitBoard {
+    unsafe {
+        *LINE
+            .get_unchecked(a.to_index() as usize)
+            .get_unchecked(b.to_index() as usize)
+    }
+}
+
+// Unit step from `a` towards `b`, if they are aligned
+const fn direction(a: Square, b: Square) -> Option<(i8, i8)> {
+    let (ax, ay) = a.to_xy();
+    let (bx, by) = b.to_xy();
+    let (dx, dy) = (bx as i8 - ax as i8, by as i8 - ay as i8);
+    if (dx == 0 && dy == 0) || (dx != 0 && dy != 0 && dx.abs() != dy.abs()) {
+        None
+    } else {
+        Some((dx.signum(), dy.signum()))
+    }
+}
+
+pub const fn between_ref(a: Square, b: Square) -> BitBoard {
+    let mut bb = BitBoard::EMPTY;
+    if let Some((dx, dy)) = direction(a, b) {
+        let mut next = a.offset(dx, dy);
+        while let Some(sq) = next {
+            if sq.to_index() == b.to_index() {
+                break;
+            }
+            bb.set_assign(sq);
+            next = sq.offset(dx, dy);
+        }
+    }
+    bb
+}
+
+pub const fn line_ref(a: Square, b: Square) -> BitBoard {
+    let mut bb = BitBoard::EMPTY;
+    if let Some((dx, dy)) = direction(a, b) {
+        bb.set_assign(a);
+        let mut next = a.offset(dx, dy);
+        while let Some(sq) = next {
+            bb.set_assign(sq);
+            next = sq.offset(dx, dy);
+        }
+        let mut next = a.offset(-dx, -dy);
+        while let Some(sq) = next {
+            bb.set_assign(sq);
+            next = sq.offset(-dx, -dy);
+        }
+    }
+    bb
+}
+
+// Function pointers can't be called in const contexts, hence the flag
+const fn mk_table(full_line: bool) -> [[BitBoard; 64]; 64] {
+    let mut table = [[BitBoard::EMPTY; 64]; 64];
+    let mut a = 0;
+    while a < 64 {
+        let mut b = 0;
+        while b < 64 {
+            let sq_a = Square::from_index(a as u8).unwrap();
+            let sq_b = Square::from_index(b as u8).unwrap();
+            table[a][b] = if full_line {
+                line_ref(sq_a, sq_b)
+            } else {
+                between_ref(sq_a, sq_b)
+            };
+            b += 1;
+        }
+        a += 1;
+    }
+    table
+}
+
+static BETWEEN: [[BitBoard; 64]; 64] = mk_table(false);
+static LINE: [[BitBoard; 64]; 64] = mk_table(true);
+
+#[cfg(test)]
+mod tests {
+    use quickcheck_macros::quickcheck;
+
+    use crate::{
+        bitboard::BitBoard,
+        coord::{File, Rank, Square},
+        pieces::line::{between, between_ref, line, line_ref},
+    };
+
+    #[test]
+    fn unit() {
+        assert_eq!(
+            between(Square::A1, Square::D4),
+            BitBoard::from_squares([Square::B2, Square::C3].into_iter())
+        );
+        assert_eq!(between(Square::E1, Square::H1).popcount(), 2);
+        assert_eq!(between(Square::E1, Square::F3), BitBoard::EMPTY);
+        assert_eq!(line(Square::B2, Square::C3).popcount(), 8);
+        assert_eq!(line(Square::E1, Square::E4), File::FE.to_bitboard());
+        assert_eq!(line(Square::A5, Square::H5), Rank::R5.to_bitboard());
+        assert_eq!(line(Square::E1, Square::F3), BitBoard::EMPTY);
+    }
+
+    #[quickcheck]
+    fn between_symmetric(a: Square, b: Square) -> bool {
+        between(a, b) == between(b, a)
+    }
+
+    #[quickcheck]
+    fn between_is_in_line(a: Square, b: Square) -> bool {
+        line(a, b).is_supserset_of(between(a, b))
+            && !between(a, b).contains(a)
+            && !between(a, b).contains(b)
+    }
+
+    #[quickcheck]
+    fn table_is_ref(a: Square, b: Square) -> bool {
+        between(a, b) == between_ref(a, b) && line(a, b) == line_ref(a, b)
+    }
+}
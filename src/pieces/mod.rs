@@ -1,6 +1,7 @@
 pub mod bishop;
 pub mod king;
 pub mod knight;
+pub mod line;
 pub mod magic_value;
 pub mod pawn;
 pub mod queen;
@@ -1,4 +1,52 @@
-use crate::{bitboard::BitBoard, coord::Rank};
+use crate::{
+    bitboard::BitBoard,
+    coord::{Rank, Square},
+    piece::Side,
+};
+
+/// The squares a pawn of the given side on `sq` attacks.
+/// Conversely, these are also the squares from which a pawn of the _other_ side attacks `sq`.
+pub fn pawn_attacks(side: Side, sq: Square) -> BitBoard {
+    pawn_attacks_cached_unsafe(side, sq)
+}
+
+#[rustfmt::skip]
+pub const fn pawn_attacks_ref(side: Side, sq: Square) -> BitBoard {
+    let mut bb = BitBoard::EMPTY;
+    let dy = match side {
+        Side::White => 1,
+        Side::Black => -1,
+    };
+
+    if let Some(sq) = sq.offset(-1, dy) { bb.set_assign(sq); }
+    if let Some(sq) = sq.offset( 1, dy) { bb.set_assign(sq); }
+
+    bb
+}
+
+static ATTACKS: [[BitBoard; 64]; 2] = {
+    let mut table = [[BitBoard::EMPTY; 64]; 2];
+    let mut i = 0;
+    while i < 64 {
+        let sq = Square::from_index(i as u8).unwrap();
+        table[Side::White as usize][i] = pawn_attacks_ref(Side::White, sq);
+        table[Side::Black as usize][i] = pawn_attacks_ref(Side::Black, sq);
+        i += 1;
+    }
+    table
+};
+
+pub const fn pawn_attacks_cached(side: Side, sq: Square) -> BitBoard {
+    ATTACKS[side as usize][sq.to_index() as usize]
+}
+
+pub fn pawn_attacks_cached_unsafe(side: Side, sq: Square) -> BitBoard {
+    unsafe {
+        *ATTACKS
+            .get_unchecked(side as usize)
+            .get_unchecked(sq.to_index() as usize)
+    }
+}
 
 pub struct PawnPushes {
     pub single: BitBoard,
@@ -93,6 +141,53 @@ impl PawnAttacks {
     }
 }
 
+#[cfg(test)]
+mod table_tests {
+    use quickcheck_macros::quickcheck;
+
+    use crate::{
+        bitboard::BitBoard,
+        coord::Square,
+        piece::Side,
+        pieces::pawn::{
+            pawn_attacks, pawn_attacks_cached, pawn_attacks_cached_unsafe, pawn_attacks_ref,
+        },
+    };
+
+    #[test]
+    fn unit() {
+        let bb = |sqs: &[Square]| BitBoard::from_squares(sqs.iter().copied());
+        assert_eq!(
+            pawn_attacks(Side::White, Square::D4),
+            bb(&[Square::C5, Square::E5])
+        );
+        assert_eq!(
+            pawn_attacks(Side::Black, Square::D4),
+            bb(&[Square::C3, Square::E3])
+        );
+        assert_eq!(pawn_attacks(Side::White, Square::A2), bb(&[Square::B3]));
+        assert_eq!(pawn_attacks(Side::Black, Square::H7), bb(&[Square::G6]));
+        assert_eq!(pawn_attacks(Side::White, Square::E8), BitBoard::EMPTY);
+    }
+
+    #[quickcheck]
+    fn symmetric_via_vflip(sq: Square) -> bool {
+        pawn_attacks(Side::White, sq) == pawn_attacks(Side::Black, sq.vflip()).vflip()
+    }
+
+    #[quickcheck]
+    fn safe_is_ref(sq: Square) -> bool {
+        pawn_attacks_cached(Side::White, sq) == pawn_attacks_ref(Side::White, sq)
+            && pawn_attacks_cached(Side::Black, sq) == pawn_attacks_ref(Side::Black, sq)
+    }
+
+    #[quickcheck]
+    fn unsafe_is_ref(sq: Square) -> bool {
+        pawn_attacks_cached_unsafe(Side::White, sq) == pawn_attacks_ref(Side::White, sq)
+            && pawn_attacks_cached_unsafe(Side::Black, sq) == pawn_attacks_ref(Side::Black, sq)
+    }
+}
+
 #[cfg(test)]
 #[cfg(never)]
 mod tests {
@@ -13,8 +13,10 @@ pub struct Position {
     pub side: Side,
     pub castling_rights: CastlingRights,
     pub en_passant_square: Option<Square>,
-    // pub halfmove_clock: u8,
-    // pub move_clock: usize,
+    /// Halfmoves since the last capture or pawn move, for the fifty-move rule
+    pub halfmove_clock: u8,
+    /// Number of completed full moves, i.e. the FEN fullmove number minus one
+    pub move_clock: usize,
 }
 
 impl std::fmt::Debug for Position {
@@ -26,14 +28,149 @@ impl std::fmt::Debug for Position {
     }
 }
 
-// TODO validate FEN after
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum PieceCountError {
     NotOneKing,
     MoreThanEightPawns,
     MoreThanSixteenPieces,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FenError {
+    UnexpectedEnd { offset: usize },
+    ExpectedSpace { offset: usize },
+    BadRankLength { offset: usize, rank: Rank },
+    BadRankCount { offset: usize },
+    UnknownPieceChar { offset: usize, char: char },
+    BadSide { offset: usize },
+    BadCastling { offset: usize },
+    BadEnPassantSquare { offset: usize },
+    BadHalfmoveClock { offset: usize },
+    BadFullmoveNumber { offset: usize },
+    TrailingGarbage { offset: usize },
+    PieceCount { side: Side, error: PieceCountError },
+}
+
+impl FenError {
+    /// Byte offset into the FEN string at which the error was detected.
+    /// Piece count errors concern the board as a whole, and so have no offset.
+    pub const fn offset(&self) -> Option<usize> {
+        match *self {
+            FenError::UnexpectedEnd { offset }
+            | FenError::ExpectedSpace { offset }
+            | FenError::BadRankLength { offset, .. }
+            | FenError::BadRankCount { offset }
+            | FenError::UnknownPieceChar { offset, .. }
+            | FenError::BadSide { offset }
+            | FenError::BadCastling { offset }
+            | FenError::BadEnPassantSquare { offset }
+            | FenError::BadHalfmoveClock { offset }
+            | FenError::BadFullmoveNumber { offset }
+            | FenError::TrailingGarbage { offset } => Some(offset),
+            FenError::PieceCount { .. } => None,
+        }
+    }
+}
+
+impl std::fmt::Display for FenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FenError::UnexpectedEnd { .. } => write!(f, "unexpected end of input")?,
+            FenError::ExpectedSpace { .. } => write!(f, "expected a space")?,
+            FenError::BadRankLength { rank, .. } => write!(
+                f,
+                "rank {} does not describe exactly 8 squares",
+                rank.to_char()
+            )?,
+            FenError::BadRankCount { .. } => write!(f, "board does not contain exactly 8 ranks")?,
+            FenError::UnknownPieceChar { char, .. } => write!(f, "unknown piece {:?}", char)?,
+            FenError::BadSide { .. } => write!(f, "side to move is not 'w' or 'b'")?,
+            FenError::BadCastling { .. } => write!(f, "invalid castling rights")?,
+            FenError::BadEnPassantSquare { .. } => write!(f, "invalid en passant square")?,
+            FenError::BadHalfmoveClock { .. } => write!(f, "invalid halfmove clock")?,
+            FenError::BadFullmoveNumber { .. } => write!(f, "invalid fullmove number")?,
+            FenError::TrailingGarbage { .. } => write!(f, "unexpected trailing characters")?,
+            FenError::PieceCount { side, error } => {
+                return write!(f, "invalid piece count for {:?}: {:?}", side, error);
+            }
+        }
+        if let Some(offset) = self.offset() {
+            write!(f, " at byte {}", offset)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for FenError {}
+
+// Runtime counterpart of ConstParser, reporting errors instead of panicking.
+struct FenParser<'a> {
+    index: usize,
+    str: &'a str,
+}
+
+impl<'a> FenParser<'a> {
+    fn new(str: &'a str) -> Self {
+        FenParser { index: 0, str }
+    }
+
+    fn finished(&self) -> bool {
+        self.index >= self.str.len()
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.str.as_bytes().get(self.index).copied()
+    }
+
+    // Pops an entire character, so that non-ASCII input can be reported as-is.
+    // The index is always on a char boundary, since everything else only ever consumes ASCII.
+    fn pop_char(&mut self) -> char {
+        let c = self.str[self.index..].chars().next().unwrap();
+        self.index += c.len_utf8();
+        c
+    }
+
+    fn pop(&mut self) -> Result<u8, FenError> {
+        let b = self
+            .peek()
+            .ok_or(FenError::UnexpectedEnd { offset: self.index })?;
+        self.index += 1;
+        Ok(b)
+    }
+
+    fn expect_space(&mut self) -> Result<(), FenError> {
+        let offset = self.index;
+        match self.pop()? {
+            b' ' => Ok(()),
+            _ => Err(FenError::ExpectedSpace { offset }),
+        }
+    }
+
+    /// Pops a field of decimal digits that has to end in either a space or the end of the input.
+    fn pop_number(&mut self, err: FenError) -> Result<u32, FenError> {
+        let mut total: u32 = 0;
+        let mut digits = 0;
+        while let Some(b) = self.peek() {
+            if b == b' ' {
+                break;
+            }
+            if !b.is_ascii_digit() {
+                return Err(err);
+            }
+            total = total
+                .checked_mul(10)
+                .and_then(|total| total.checked_add((b - b'0') as u32))
+                .ok_or(err)?;
+            digits += 1;
+            self.index += 1;
+        }
+        if digits == 0 {
+            return Err(err);
+        }
+        Ok(total)
+    }
+}
+
 struct ConstParser<'a> {
     index: usize,
     bytes: &'a [u8],
@@ -170,11 +307,174 @@ impl Position {
             side,
             castling_rights,
             en_passant_square,
-            // halfmove_clock,
-            // move_clock,
+            halfmove_clock,
+            move_clock,
         }
     }
 
+    /// Fallible version of [`Position::from_fen`], for FEN strings from untrusted sources.
+    /// Unlike `from_fen`, castling rights must be either `-` or a combination of `KQkq` without
+    /// repetitions, and the en passant square has to be on the rank a double push of the side
+    /// that just moved skips (the sixth with White to move, the third with Black to move),
+    /// though there doesn't have to be a pawn in front of it. The two clock fields may be
+    /// omitted, in which case they default to `0 1`.
+    pub fn parse_fen(fen: &str) -> Result<Position, FenError> {
+        let mut fen = FenParser::new(fen.trim_end());
+
+        let pieces = {
+            let mut pieces = [None; 64];
+            let mut rank: i8 = 7;
+            let mut file: i8 = 0;
+
+            loop {
+                let offset = fen.index;
+                let c = match fen.peek() {
+                    None | Some(b' ') => {
+                        if file != 8 {
+                            let rank = Rank::from_index(rank as u8).unwrap();
+                            return Err(FenError::BadRankLength { offset, rank });
+                        }
+                        if rank != 0 {
+                            return Err(FenError::BadRankCount { offset });
+                        }
+                        break;
+                    }
+                    Some(_) => fen.pop_char(),
+                };
+                if c == '/' {
+                    if file != 8 {
+                        let rank = Rank::from_index(rank as u8).unwrap();
+                        return Err(FenError::BadRankLength { offset, rank });
+                    }
+                    if rank == 0 {
+                        return Err(FenError::BadRankCount { offset });
+                    }
+                    rank -= 1;
+                    file = 0;
+                } else if let Some(digit) = c.to_digit(10).filter(|d| (1..=8).contains(d)) {
+                    file += digit as i8;
+                } else if let Some(piece) = Piece::from_fen_char(c) {
+                    if file < 8 {
+                        pieces[(rank * 8 + file) as usize] = Some(piece);
+                    }
+                    file += 1;
+                } else {
+                    return Err(FenError::UnknownPieceChar { offset, char: c });
+                }
+                if file > 8 {
+                    let rank = Rank::from_index(rank as u8).unwrap();
+                    return Err(FenError::BadRankLength { offset, rank });
+                }
+            }
+            pieces
+        };
+        fen.expect_space()?;
+
+        let side_offset = fen.index;
+        let side = match fen.pop()? {
+            b'w' => Side::White,
+            b'b' => Side::Black,
+            _ => {
+                return Err(FenError::BadSide {
+                    offset: side_offset,
+                });
+            }
+        };
+        if fen.peek().is_some_and(|b| b != b' ') {
+            return Err(FenError::BadSide {
+                offset: side_offset,
+            });
+        }
+        fen.expect_space()?;
+
+        let castling_rights = {
+            let mut rights = CastlingRights::new_empty();
+            let offset = fen.index;
+            if fen.peek() == Some(b'-') {
+                fen.index += 1;
+            } else {
+                let mut seen = 0u8;
+                while let Some(b) = fen.peek().filter(|&b| b != b' ') {
+                    let (bit, side, castling_side) = match b {
+                        b'K' => (1, Side::White, CastlingSide::KingSide),
+                        b'Q' => (2, Side::White, CastlingSide::QueenSide),
+                        b'k' => (4, Side::Black, CastlingSide::KingSide),
+                        b'q' => (8, Side::Black, CastlingSide::QueenSide),
+                        _ => return Err(FenError::BadCastling { offset: fen.index }),
+                    };
+                    if seen & bit != 0 {
+                        return Err(FenError::BadCastling { offset: fen.index });
+                    }
+                    seen |= bit;
+                    rights.restore(side, castling_side);
+                    fen.index += 1;
+                }
+                if seen == 0 {
+                    return Err(FenError::BadCastling { offset });
+                }
+            }
+            rights
+        };
+        fen.expect_space()?;
+
+        let en_passant_square = {
+            let offset = fen.index;
+            let err = FenError::BadEnPassantSquare { offset };
+            match fen.pop()? {
+                b'-' => None,
+                b => {
+                    let file = File::from_ascii(b).ok_or(err)?;
+                    let rank = fen.pop().ok().and_then(Rank::from_ascii).ok_or(err)?;
+                    let expected_rank = match side {
+                        Side::White => Rank::R6,
+                        Side::Black => Rank::R3,
+                    };
+                    if rank != expected_rank {
+                        return Err(err);
+                    }
+                    Some(Square::from_coord(file, rank))
+                }
+            }
+        };
+        if fen.peek().is_some_and(|b| b != b' ') {
+            return Err(FenError::BadEnPassantSquare { offset: fen.index });
+        }
+
+        // The clocks are optional, since plenty of tools (and EPD) leave them out
+        let (halfmove_clock, move_clock) = if fen.finished() {
+            (0, 0)
+        } else {
+            fen.expect_space()?;
+            let offset = fen.index;
+            let err = FenError::BadHalfmoveClock { offset };
+            let halfmove_clock = u8::try_from(fen.pop_number(err)?).map_err(|_| err)?;
+            fen.expect_space()?;
+            let offset = fen.index;
+            let fullmove_number = fen.pop_number(FenError::BadFullmoveNumber { offset })?;
+            if fullmove_number == 0 {
+                return Err(FenError::BadFullmoveNumber { offset });
+            }
+            (halfmove_clock, fullmove_number as usize - 1)
+        };
+
+        if !fen.finished() {
+            return Err(FenError::TrailingGarbage { offset: fen.index });
+        }
+
+        let position = Position {
+            pieces,
+            side,
+            castling_rights,
+            en_passant_square,
+            halfmove_clock,
+            move_clock,
+        };
+        position
+            .check_piece_count()
+            .map_err(|(side, error)| FenError::PieceCount { side, error })?;
+        Ok(position)
+    }
+
     pub fn to_fen(&self) -> String {
         let mut fen = String::with_capacity(80);
         for rank_idx in (0..8).rev() {
@@ -198,6 +498,36 @@ impl Position {
                 fen.push('/');
             }
         }
+
+        fen.push(' ');
+        fen.push(match self.side {
+            Side::White => 'w',
+            Side::Black => 'b',
+        });
+
+        fen.push(' ');
+        let len = fen.len();
+        for (side, castling_side, char) in [
+            (Side::White, CastlingSide::KingSide, 'K'),
+            (Side::White, CastlingSide::QueenSide, 'Q'),
+            (Side::Black, CastlingSide::KingSide, 'k'),
+            (Side::Black, CastlingSide::QueenSide, 'q'),
+        ] {
+            if self.castling_rights.can_castle(side, castling_side) {
+                fen.push(char);
+            }
+        }
+        if fen.len() == len {
+            fen.push('-');
+        }
+
+        fen.push(' ');
+        match self.en_passant_square {
+            Some(sq) => fen.push_str(&sq.to_string()),
+            None => fen.push('-'),
+        }
+
+        fen.push_str(&format!(" {} {}", self.halfmove_clock, self.move_clock + 1));
         fen
     }
 
@@ -231,6 +561,13 @@ impl Position {
     }
 }
 
+impl std::str::FromStr for Position {
+    type Err = FenError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Position::parse_fen(s)
+    }
+}
+
 #[cfg(test)]
 impl Position {
     //   ╔════════════════════════╗
@@ -316,3 +653,160 @@ impl Position {
         "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        coord::Rank,
+        piece::Side,
+        position::{FenError, PieceCountError, Position},
+    };
+
+    #[test]
+    fn parse_fen_matches_from_fen() {
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+            "k4n1n/2P1P1P1/3b4/4Pp2/8/8/2P5/K7 w - f6 0 1",
+        ] {
+            assert_eq!(Position::parse_fen(fen), Ok(Position::from_fen(fen)));
+            assert_eq!(fen.parse::<Position>(), Ok(Position::from_fen(fen)));
+        }
+    }
+
+    #[test]
+    fn fen_roundtrip() {
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 b - - 99 250",
+        ] {
+            let pos = Position::parse_fen(fen).unwrap();
+            assert_eq!(pos.to_fen(), fen);
+            assert_eq!(pos.to_node().to_position(), pos);
+            assert_eq!(pos.to_node().to_position().to_fen(), fen);
+        }
+    }
+
+    #[test]
+    fn clocks() {
+        assert_eq!(Position::POSITION_5.halfmove_clock, 1);
+        assert_eq!(Position::POSITION_5.move_clock, 7);
+        assert_eq!(Position::POSITION_6.move_clock, 9);
+        assert_eq!(
+            Position::parse_fen("8/8/8/8/8/8/8/K6k w - - 256 1"),
+            Err(FenError::BadHalfmoveClock { offset: 24 })
+        );
+    }
+
+    #[test]
+    fn parse_fen_optional_clocks() {
+        assert_eq!(
+            Position::parse_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -\n"),
+            Ok(Position::START_POS)
+        );
+    }
+
+    #[test]
+    fn parse_fen_errors() {
+        fn err(fen: &str) -> FenError {
+            Position::parse_fen(fen).unwrap_err()
+        }
+        use FenError::*;
+        assert_eq!(
+            err("rnbqkbnr/ppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"),
+            BadRankLength {
+                offset: 16,
+                rank: Rank::R7
+            }
+        );
+        assert_eq!(
+            err("rnbqkbnr/pppppppp/9/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"),
+            UnknownPieceChar {
+                offset: 18,
+                char: '9'
+            }
+        );
+        assert_eq!(
+            err("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP w KQkq - 0 1"),
+            BadRankCount { offset: 34 }
+        );
+        assert_eq!(
+            err("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR/8 w KQkq - 0 1"),
+            BadRankCount { offset: 43 }
+        );
+        assert_eq!(
+            err("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNX w KQkq - 0 1"),
+            UnknownPieceChar {
+                offset: 42,
+                char: 'X'
+            }
+        );
+        assert_eq!(
+            err("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBN♔ w KQkq - 0 1"),
+            UnknownPieceChar {
+                offset: 42,
+                char: '♔'
+            }
+        );
+        assert_eq!(
+            err("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR x KQkq - 0 1"),
+            BadSide { offset: 44 }
+        );
+        assert_eq!(
+            err("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkK - 0 1"),
+            BadCastling { offset: 49 }
+        );
+        assert_eq!(
+            err("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQ-q - 0 1"),
+            BadCastling { offset: 48 }
+        );
+        assert_eq!(
+            err("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq e3 0 1"),
+            BadEnPassantSquare { offset: 51 }
+        );
+        assert_eq!(
+            err("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq i6 0 1"),
+            BadEnPassantSquare { offset: 51 }
+        );
+        assert_eq!(
+            err("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - x 1"),
+            BadHalfmoveClock { offset: 53 }
+        );
+        assert_eq!(
+            err("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 0"),
+            BadFullmoveNumber { offset: 55 }
+        );
+        assert_eq!(
+            err("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 garbage"),
+            TrailingGarbage { offset: 56 }
+        );
+        assert_eq!(
+            err("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0"),
+            UnexpectedEnd { offset: 54 }
+        );
+        assert_eq!(
+            err("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w  KQkq - 0 1"),
+            BadCastling { offset: 46 }
+        );
+        assert_eq!(
+            err("rnbqqbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"),
+            PieceCount {
+                side: Side::Black,
+                error: PieceCountError::NotOneKing
+            }
+        );
+    }
+
+    #[test]
+    fn fen_error_display() {
+        let err = Position::parse_fen("8/8/8/8/8/8/8/8 w - - 0 1 x").unwrap_err();
+        assert_eq!(err.to_string(), "unexpected trailing characters at byte 25");
+    }
+}
@@ -0,0 +1,251 @@
+use crate::{
+    coord::{File, Rank, Square},
+    moves::{Move, MoveType},
+    perft::node::Node,
+    piece::{Piece, PieceType, Side},
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SanError {
+    /// Not syntactically a SAN move
+    Invalid,
+    /// No legal move matches
+    Illegal,
+    /// More than one legal move matches
+    Ambiguous,
+}
+
+impl std::fmt::Display for SanError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SanError::Invalid => write!(f, "invalid SAN"),
+            SanError::Illegal => write!(f, "illegal move"),
+            SanError::Ambiguous => write!(f, "ambiguous move"),
+        }
+    }
+}
+
+impl std::error::Error for SanError {}
+
+const fn piece_char(piece_type: PieceType) -> char {
+    Piece::from_side_piece(Side::White, piece_type).to_fen_char()
+}
+
+/// Parses a move in Standard Algebraic Notation, like `Nbd7`, `exd6`, `e8=Q+` or `O-O`, in the
+/// context of `node`.
+/// Check and annotation suffixes are accepted but not verified.
+pub fn parse_san(node: &Node, san: &str) -> Result<Move, SanError> {
+    let san = san.trim_end_matches(['+', '#', '!', '?']);
+
+    let castle = match san {
+        "O-O" | "0-0" => Some(MoveType::CastleKingside),
+        "O-O-O" | "0-0-0" => Some(MoveType::CastleQueenside),
+        _ => None,
+    };
+    if let Some(kind) = castle {
+        return unique(node, |mv| mv.kind() == kind);
+    }
+
+    if !san.is_ascii() {
+        return Err(SanError::Invalid);
+    }
+    let mut rest = san.as_bytes();
+
+    // Promotion, with or without the '='
+    let mut promotion = None;
+    if let [init @ .., last] = rest
+        && let Some(piece) = Piece::from_fen_char(*last as char)
+        && matches!(
+            piece,
+            Piece::WhiteKnight | Piece::WhiteBishop | Piece::WhiteRook | Piece::WhiteQueen
+        )
+    {
+        promotion = Some(piece.piece_type());
+        rest = init.strip_suffix(b"=").unwrap_or(init);
+    }
+
+    let [init @ .., file, rank] = rest else {
+        return Err(SanError::Invalid);
+    };
+    let to = Square::from_chars(*file as char, *rank as char).ok_or(SanError::Invalid)?;
+    rest = init;
+
+    let piece_type = match rest.first() {
+        Some(&c @ (b'N' | b'B' | b'R' | b'Q' | b'K')) => {
+            rest = &rest[1..];
+            Piece::from_fen_char(c as char).unwrap().piece_type()
+        }
+        _ => PieceType::Pawn,
+    };
+    rest = rest.strip_suffix(b"x").unwrap_or(rest);
+
+    let (mut from_file, mut from_rank) = (None, None);
+    for &c in rest {
+        match (File::from_ascii(c), Rank::from_ascii(c)) {
+            (Some(file), _) if from_file.is_none() && from_rank.is_none() => from_file = Some(file),
+            (_, Some(rank)) if from_rank.is_none() => from_rank = Some(rank),
+            _ => return Err(SanError::Invalid),
+        }
+    }
+
+    let piece = Piece::from_side_piece(node.side, piece_type);
+    unique(node, |mv| {
+        let (rank, file) = mv.from().to_coord();
+        mv.to() == to
+            && !mv.is_castle()
+            && mv.promotion() == promotion
+            && node.piece(piece).contains(mv.from())
+            && from_file.is_none_or(|f| f == file)
+            && from_rank.is_none_or(|r| r == rank)
+    })
+}
+
+fn unique<F: Fn(Move) -> bool>(node: &Node, f: F) -> Result<Move, SanError> {
+    let moves = node.legal_moves();
+    let mut matches = moves.iter().copied().filter(|&mv| f(mv));
+    match (matches.next(), matches.next()) {
+        (Some(mv), None) => Ok(mv),
+        (None, _) => Err(SanError::Illegal),
+        (Some(_), Some(_)) => Err(SanError::Ambiguous),
+    }
+}
+
+/// Formats the legal move `mv` in Standard Algebraic Notation, including check and mate
+/// suffixes and the minimal disambiguation.
+pub fn to_san(node: &Node, mv: Move) -> String {
+    let mut san = match mv.kind() {
+        MoveType::CastleKingside => "O-O".to_string(),
+        MoveType::CastleQueenside => "O-O-O".to_string(),
+        _ => {
+            let piece = node.find_piece(node.side, mv.from().to_bitboard());
+            debug_assert!(piece.is_some());
+            let piece_type = piece.map_or(PieceType::Pawn, Piece::piece_type);
+            let (from_rank, from_file) = mv.from().to_coord();
+            let mut san = String::new();
+            if piece_type == PieceType::Pawn {
+                if mv.is_capture() {
+                    san.push(from_file.to_char());
+                }
+            } else {
+                san.push(piece_char(piece_type));
+                let others: Vec<Square> = node
+                    .legal_moves()
+                    .iter()
+                    .filter(|other| {
+                        other.to() == mv.to()
+                            && other.from() != mv.from()
+                            && node
+                                .find_piece(node.side, other.from().to_bitboard())
+                                .is_some_and(|p| p.piece_type() == piece_type)
+                    })
+                    .map(|other| other.from())
+                    .collect();
+                if !others.is_empty() {
+                    if others.iter().all(|sq| sq.to_coord().1 != from_file) {
+                        san.push(from_file.to_char());
+                    } else if others.iter().all(|sq| sq.to_coord().0 != from_rank) {
+                        san.push(from_rank.to_char());
+                    } else {
+                        san.push(from_file.to_char());
+                        san.push(from_rank.to_char());
+                    }
+                }
+            }
+            if mv.is_capture() {
+                san.push('x');
+            }
+            san.push_str(&mv.to().to_string());
+            if let Some(promotion) = mv.promotion() {
+                san.push('=');
+                san.push(piece_char(promotion));
+            }
+            san
+        }
+    };
+
+    let child = node.make_move(mv);
+    if child.king_attacked(child.side) {
+        san.push(if child.legal_moves().is_empty() {
+            '#'
+        } else {
+            '+'
+        });
+    }
+    san
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        coord::Square,
+        moves::{Move, MoveType},
+        perft::node::Node,
+        position::Position,
+        san::{SanError, parse_san, to_san},
+    };
+
+    #[test]
+    fn roundtrip() {
+        for node in [
+            Node::POSITION_1,
+            Node::POSITION_2,
+            Node::POSITION_3,
+            Node::POSITION_4,
+            Node::POSITION_5,
+            Node::POSITION_6,
+        ] {
+            for &mv in &node.legal_moves() {
+                assert_eq!(parse_san(&node, &to_san(&node, mv)), Ok(mv));
+            }
+        }
+    }
+
+    #[test]
+    fn unit() {
+        let node = Node::POSITION_2;
+        assert_eq!(
+            parse_san(&node, "O-O-O"),
+            Ok(Move::new(Square::E1, Square::C1, MoveType::CastleQueenside))
+        );
+        assert_eq!(
+            parse_san(&node, "Bxa6"),
+            Ok(Move::new(Square::E2, Square::A6, MoveType::Capture))
+        );
+        assert_eq!(
+            parse_san(&node, "dxe6!?"),
+            Ok(Move::new(Square::D5, Square::E6, MoveType::Capture))
+        );
+        assert_eq!(parse_san(&node, "Qa8"), Err(SanError::Illegal));
+        assert_eq!(
+            parse_san(&node, "Rb1"),
+            Ok(Move::new(Square::A1, Square::B1, MoveType::Quiet))
+        );
+        assert_eq!(parse_san(&node, "e9"), Err(SanError::Invalid));
+        assert_eq!(parse_san(&node, ""), Err(SanError::Invalid));
+
+        // Both knights can reach d7
+        let node = Position::parse_fen("4k3/8/8/8/8/8/8/1N1NK3 w - - 0 1")
+            .unwrap()
+            .to_node();
+        assert_eq!(parse_san(&node, "Nc3"), Err(SanError::Ambiguous));
+        let mv = Move::new(Square::B1, Square::C3, MoveType::Quiet);
+        assert_eq!(parse_san(&node, "Nbc3"), Ok(mv));
+        assert_eq!(parse_san(&node, "Nb1c3"), Ok(mv));
+        assert_eq!(to_san(&node, mv), "Nbc3");
+
+        let node = Node::POSITION_4;
+        let promo = Move::new(Square::B2, Square::A1, MoveType::PromoteCaptureQueen);
+        let node = node.make_move(Move::new(Square::G1, Square::H1, MoveType::Quiet));
+        assert_eq!(to_san(&node, promo), "bxa1=Q");
+        assert_eq!(parse_san(&node, "bxa1=Q"), Ok(promo));
+        assert_eq!(parse_san(&node, "bxa1Q"), Ok(promo));
+
+        // Fool's mate
+        let mut node = Node::POSITION_1;
+        for san in ["f3", "e5", "g4"] {
+            node = node.make_move(parse_san(&node, san).unwrap());
+        }
+        let mate = parse_san(&node, "Qh4").unwrap();
+        assert_eq!(to_san(&node, mate), "Qh4#");
+    }
+}
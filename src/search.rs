@@ -0,0 +1,611 @@
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    eval::Evaluator,
+    moves::{Move, MoveList},
+    perft::node::Node,
+    piece::{Piece, PieceType},
+    tt::{Bound, TranspositionTable, TtEntry},
+};
+
+/// Larger than any score a search can return.
+pub const INFINITY: i32 = 32_000;
+/// Score for delivering mate right now. Mate in `n` plies scores `MATE - n`, so shorter mates
+/// score higher.
+pub const MATE: i32 = 31_000;
+pub const MAX_PLY: usize = 128;
+
+// Scores this close to MATE are mate scores
+const MATE_BOUND: i32 = MATE - MAX_PLY as i32;
+
+/// Signed number of moves until mate, as UCI reports it, if `score` is a mate score.
+/// Negative if the side to move is getting mated.
+pub const fn mate_in(score: i32) -> Option<i32> {
+    if score > MATE_BOUND {
+        Some((MATE - score + 1) / 2)
+    } else if score < -MATE_BOUND {
+        Some(-(MATE + score) / 2)
+    } else {
+        None
+    }
+}
+
+/// When to stop searching. The search always completes at least depth 1, so that there is a
+/// move to play, even if a limit has already been exceeded by then.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Limits {
+    pub depth: Option<u8>,
+    pub nodes: Option<u64>,
+    pub time: Option<Duration>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SearchResult {
+    /// `None` if there are no legal moves
+    pub best_move: Option<Move>,
+    /// Centipawns from the side to move's point of view, see [`mate_in`] for mate scores
+    pub score: i32,
+    pub pv: Vec<Move>,
+    pub depth: u8,
+    pub nodes: u64,
+}
+
+/// Searches `node` with iterative deepening until one of the `limits` is hit or `stop` is set,
+/// calling `on_iteration` with the result of every completed depth.
+///
+/// `history` holds the hashes of the positions leading up to `node`, oldest first, and is used to
+/// detect repetitions.
+pub fn search<F: FnMut(&SearchResult)>(
+    node: &Node,
+    history: &[u64],
+    tt: &TranspositionTable,
+    limits: Limits,
+    stop: &AtomicBool,
+    mut on_iteration: F,
+) -> SearchResult {
+    tt.new_search();
+    let mut search = Search::new(history, tt, limits, stop);
+    let mut node = node.clone();
+    let max_depth = limits
+        .depth
+        .unwrap_or(MAX_PLY as u8 - 1)
+        .clamp(1, MAX_PLY as u8 - 1);
+
+    let mut result = SearchResult {
+        best_move: None,
+        score: 0,
+        pv: Vec::new(),
+        depth: 0,
+        nodes: 0,
+    };
+    for depth in 1..=max_depth {
+        let score = search.negamax(&mut node, depth, 0, -INFINITY, INFINITY);
+        if search.aborted {
+            break;
+        }
+        let mut pv = search.pv[0][..search.pv_len[0]].to_vec();
+        extend_pv(&node, &mut pv, tt, depth);
+        result = SearchResult {
+            best_move: pv.first().copied(),
+            score,
+            pv,
+            depth,
+            nodes: search.nodes,
+        };
+        on_iteration(&result);
+
+        // No point in searching deeper once a forced mate has been found, or if there's nothing
+        // to search at all
+        if result.best_move.is_none()
+            || mate_in(score).is_some_and(|n| n.unsigned_abs() * 2 <= depth as u32)
+        {
+            break;
+        }
+        if stop.load(Ordering::Relaxed) {
+            break;
+        }
+        // An iteration takes longer than all the previous ones combined, so don't start one we
+        // can't finish
+        if let Some(time) = limits.time
+            && search.start.elapsed() > time / 2
+        {
+            break;
+        }
+    }
+    result.nodes = search.nodes;
+    result
+}
+
+// Cutoffs on transposition table hits cut the PV short, so fill in the rest from the table
+fn extend_pv(node: &Node, pv: &mut Vec<Move>, tt: &TranspositionTable, depth: u8) {
+    let mut node = node.clone();
+    for &mv in pv.iter() {
+        node.make(mv);
+    }
+    while pv.len() < depth as usize
+        && let Some(entry) = tt.probe(node.hash)
+        && node.legal_moves().contains(entry.best_move)
+    {
+        node.make(entry.best_move);
+        pv.push(entry.best_move);
+    }
+}
+
+// Mate scores are stored relative to the node rather than the root, so they stay correct when
+// the same position is reached at a different ply
+const fn score_to_tt(score: i32, ply: usize) -> i32 {
+    if score > MATE_BOUND {
+        score + ply as i32
+    } else if score < -MATE_BOUND {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+const fn score_from_tt(score: i32, ply: usize) -> i32 {
+    if score > MATE_BOUND {
+        score - ply as i32
+    } else if score < -MATE_BOUND {
+        score + ply as i32
+    } else {
+        score
+    }
+}
+
+struct Search<'a> {
+    tt: &'a TranspositionTable,
+    evaluator: Evaluator,
+    limits: Limits,
+    stop: &'a AtomicBool,
+    start: Instant,
+    nodes: u64,
+    /// Set once a limit is hit, after which all scores are meaningless
+    aborted: bool,
+    /// Whether a limit is allowed to abort the search, only false during the first iteration
+    can_abort: bool,
+    /// Hashes of the game so far and the current search path
+    history: Vec<u64>,
+    /// Triangular PV table, `pv[ply]` holds the best line from `ply` onwards
+    pv: Vec<[Move; MAX_PLY]>,
+    pv_len: [usize; MAX_PLY],
+}
+
+impl<'a> Search<'a> {
+    // How often to look at the clock and the stop flag
+    const CHECK_INTERVAL: u64 = 1024;
+    const DELTA_MARGIN: i32 = 200;
+
+    fn new(
+        history: &[u64],
+        tt: &'a TranspositionTable,
+        limits: Limits,
+        stop: &'a AtomicBool,
+    ) -> Self {
+        Search {
+            tt,
+            evaluator: Evaluator::new(),
+            limits,
+            stop,
+            start: Instant::now(),
+            nodes: 0,
+            aborted: false,
+            can_abort: false,
+            history: history.to_vec(),
+            pv: vec![[Move::NULL; MAX_PLY]; MAX_PLY],
+            pv_len: [0; MAX_PLY],
+        }
+    }
+
+    fn should_abort(&mut self) -> bool {
+        if self.aborted {
+            return true;
+        }
+        if !self.can_abort || !self.nodes.is_multiple_of(Self::CHECK_INTERVAL) {
+            return false;
+        }
+        self.aborted = self.stop.load(Ordering::Relaxed)
+            || self.limits.nodes.is_some_and(|nodes| self.nodes >= nodes)
+            || self
+                .limits
+                .time
+                .is_some_and(|time| self.start.elapsed() >= time);
+        self.aborted
+    }
+
+    // Fifty-move rule, or a repetition of any earlier position, which is treated as a draw
+    // immediately since the side that repeated could repeat again
+    fn is_draw(&self, node: &Node) -> bool {
+        if node.halfmove_clock >= 100 {
+            return true;
+        }
+        let Some((&current, earlier)) = self.history.split_last() else {
+            return false;
+        };
+        // Only positions since the last irreversible move can repeat, and only those with the
+        // same side to move
+        earlier
+            .iter()
+            .rev()
+            .take(node.halfmove_clock as usize)
+            .skip(1)
+            .step_by(2)
+            .any(|&hash| hash == current)
+    }
+
+    fn negamax(
+        &mut self,
+        node: &mut Node,
+        depth: u8,
+        ply: usize,
+        mut alpha: i32,
+        beta: i32,
+    ) -> i32 {
+        self.pv_len[ply] = ply;
+        if self.should_abort() {
+            return 0;
+        }
+
+        if ply > 0 {
+            if self.is_draw(node) {
+                return 0;
+            }
+            // Mate distance pruning: even mating right now can't beat a shorter mate found
+            // elsewhere
+            let alpha = alpha.max(-MATE + ply as i32);
+            let beta = beta.min(MATE - ply as i32 - 1);
+            if alpha >= beta {
+                return alpha;
+            }
+        }
+
+        if depth == 0 || ply >= MAX_PLY - 1 {
+            return self.quiescence(node, ply, alpha, beta);
+        }
+        self.nodes += 1;
+
+        let entry = self.tt.probe(node.hash);
+        // Never cut at the root, which has to come up with a move
+        if ply > 0
+            && let Some(entry) = entry
+            && entry.depth >= depth
+        {
+            let score = score_from_tt(entry.score, ply);
+            match entry.bound {
+                Bound::Exact => return score,
+                Bound::Lower if score >= beta => return score,
+                Bound::Upper if score <= alpha => return score,
+                _ => {}
+            }
+        }
+        let tt_move = entry.map_or(Move::NULL, |entry| entry.best_move);
+
+        let mut moves = node.legal_moves();
+        if moves.is_empty() {
+            return if node.king_attacked(node.side) {
+                -MATE + ply as i32
+            } else {
+                0
+            };
+        }
+        self.order_moves(node, &mut moves, tt_move);
+
+        let original_alpha = alpha;
+        let mut best = -INFINITY;
+        let mut best_move = Move::NULL;
+        for &mv in &moves {
+            let undo = node.make(mv);
+            self.history.push(node.hash);
+            let score = -self.negamax(node, depth - 1, ply + 1, -beta, -alpha);
+            self.history.pop();
+            node.unmake(mv, undo);
+
+            if self.aborted {
+                return 0;
+            }
+            if score > best {
+                best = score;
+                if score > alpha {
+                    alpha = score;
+                    best_move = mv;
+                    self.update_pv(ply, mv);
+                    if alpha >= beta {
+                        break;
+                    }
+                }
+            }
+        }
+
+        let bound = if best >= beta {
+            Bound::Lower
+        } else if best > original_alpha {
+            Bound::Exact
+        } else {
+            Bound::Upper
+        };
+        self.tt.store(
+            node.hash,
+            TtEntry {
+                depth,
+                bound,
+                score: score_to_tt(best, ply),
+                best_move,
+            },
+        );
+        if ply == 0 {
+            self.can_abort = true;
+        }
+        best
+    }
+
+    // Only searches captures and promotions, so that the position is quiet by the time it is
+    // evaluated. Since the side to move isn't forced to capture, the static evaluation is a lower
+    // bound on the score, unless it's in check.
+    fn quiescence(&mut self, node: &mut Node, ply: usize, mut alpha: i32, beta: i32) -> i32 {
+        self.pv_len[ply] = ply;
+        if self.should_abort() {
+            return 0;
+        }
+        self.nodes += 1;
+        if ply >= MAX_PLY - 1 {
+            return self.evaluator.evaluate(node);
+        }
+
+        let in_check = node.king_attacked(node.side);
+        let stand_pat = if in_check {
+            -INFINITY
+        } else {
+            self.evaluator.evaluate(node)
+        };
+        if stand_pat >= beta {
+            return stand_pat;
+        }
+        alpha = alpha.max(stand_pat);
+
+        // Every evasion is searched when in check, standing pat is not an option there
+        let mut moves = if in_check {
+            node.legal_moves()
+        } else {
+            node.legal_captures()
+        };
+        if in_check && moves.is_empty() {
+            return -MATE + ply as i32;
+        }
+        self.order_moves(node, &mut moves, Move::NULL);
+
+        let mut best = stand_pat;
+        for &mv in &moves {
+            // Delta pruning: skip captures that can't raise alpha even with a margin for
+            // positional gains. Captures that lose material in the exchange are skipped too.
+            if !in_check
+                && mv.promotion().is_none()
+                && (stand_pat + captured_value(node, mv) + Self::DELTA_MARGIN <= alpha
+                    || !node.see_ge(mv, 0))
+            {
+                continue;
+            }
+
+            let undo = node.make(mv);
+            let score = -self.quiescence(node, ply + 1, -beta, -alpha);
+            node.unmake(mv, undo);
+
+            if self.aborted {
+                return 0;
+            }
+            if score > best {
+                best = score;
+                if score > alpha {
+                    alpha = score;
+                    self.update_pv(ply, mv);
+                    if alpha >= beta {
+                        break;
+                    }
+                }
+            }
+        }
+        best
+    }
+
+    fn update_pv(&mut self, ply: usize, mv: Move) {
+        let child_len = self.pv_len[ply + 1];
+        let (head, tail) = self.pv.split_at_mut(ply + 1);
+        head[ply][ply] = mv;
+        head[ply][ply + 1..child_len].copy_from_slice(&tail[0][ply + 1..child_len]);
+        self.pv_len[ply] = child_len;
+    }
+
+    // The transposition table move first, then captures of the most valuable victims by the least
+    // valuable attackers, then quiet moves, and captures that lose material in the exchange last
+    fn order_moves(&self, node: &Node, moves: &mut MoveList, tt_move: Move) {
+        let key = |mv: &Move| -> i32 {
+            if *mv == tt_move {
+                return i32::MIN;
+            }
+            if !mv.is_capture() {
+                return 0;
+            }
+            let see = node.see(*mv);
+            if see < 0 {
+                return -see;
+            }
+            let attacker = node
+                .find_piece(node.side, mv.from().to_bitboard())
+                .map_or(PieceType::Pawn, Piece::piece_type);
+            -(captured_value(node, *mv) * 16 - attacker.value() / 16)
+        };
+        moves.as_mut_slice().sort_by_cached_key(key);
+    }
+}
+
+// En passant is the one capture where there's no piece on the target square
+fn captured_value(node: &Node, mv: Move) -> i32 {
+    node.find_piece(node.side.opponent(), mv.to().to_bitboard())
+        .map_or(0, |piece| piece.piece_type().value())
+        .max(if mv.is_capture() {
+            PieceType::Pawn.value()
+        } else {
+            0
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::atomic::AtomicBool, time::Duration};
+
+    use crate::{
+        position::Position,
+        search::{Limits, MATE, SearchResult, mate_in, search},
+        tt::TranspositionTable,
+        uci::parse_uci_move,
+    };
+
+    fn search_fen(fen: &str, limits: Limits) -> SearchResult {
+        let node = Position::parse_fen(fen).unwrap().to_node();
+        let stop = AtomicBool::new(false);
+        let tt = TranspositionTable::new(1);
+        let result = search(&node, &[node.hash], &tt, limits, &stop, |_| {});
+        // The PV has to be a legal line
+        let mut child = node.clone();
+        for &mv in &result.pv {
+            assert!(
+                child.legal_moves().contains(mv),
+                "{:?} in {:?}",
+                mv,
+                result.pv
+            );
+            child.make(mv);
+        }
+        result
+    }
+
+    fn depth(depth: u8) -> Limits {
+        Limits {
+            depth: Some(depth),
+            ..Limits::default()
+        }
+    }
+
+    #[test]
+    fn mate_scores() {
+        assert_eq!(mate_in(MATE - 1), Some(1));
+        assert_eq!(mate_in(MATE - 3), Some(2));
+        assert_eq!(mate_in(-MATE + 2), Some(-1));
+        assert_eq!(mate_in(-MATE + 4), Some(-2));
+        assert_eq!(mate_in(0), None);
+        assert_eq!(mate_in(-900), None);
+    }
+
+    #[test]
+    fn finds_mates() {
+        // Back rank mate
+        let result = search_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", depth(3));
+        assert_eq!(result.best_move.unwrap().to_string(), "a1a8");
+        assert_eq!(result.score, MATE - 1);
+
+        // Mate in 2, with a knight sacrifice
+        let result = search_fen(
+            "r2qkb1r/pp2nppp/3p4/2pNN1B1/2BnP3/3P4/PPP2PPP/R2bK2R w KQkq - 1 1",
+            depth(4),
+        );
+        assert_eq!(mate_in(result.score), Some(2));
+        assert_eq!(result.pv.len(), 3);
+
+        // Getting mated, whatever we do
+        let result = search_fen("7k/6Q1/5K2/8/8/8/8/8 b - - 0 1", depth(3));
+        assert_eq!(result.score, -MATE);
+        assert_eq!(result.best_move, None);
+    }
+
+    #[test]
+    fn draws() {
+        // Stalemate
+        let result = search_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1", depth(3));
+        assert_eq!((result.best_move, result.score), (None, 0));
+
+        // The fifty-move rule makes the extra rook worthless
+        let result = search_fen("8/8/8/3k4/8/8/8/R3K3 b - - 99 80", depth(2));
+        assert_eq!(result.score, 0);
+        let result = search_fen("8/8/8/3k4/8/8/8/R3K3 b - - 0 80", depth(2));
+        assert!(result.score < -400, "{}", result.score);
+
+        // White is down material and getting mated, but has a perpetual check
+        let result = search_fen("7k/6p1/8/7Q/8/8/rr6/6K1 b - - 0 1", depth(6));
+        assert_eq!(result.score, 0);
+    }
+
+    #[test]
+    fn wins_material() {
+        let result = search_fen("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1", depth(2));
+        assert_eq!(result.best_move.unwrap().to_string(), "d2d5");
+        assert!(result.score > 400, "{}", result.score);
+
+        // The knight is defended, which quiescence search sees even at depth 1
+        let result = search_fen("4k3/4p3/3n4/8/8/8/3Q4/4K3 w - - 0 1", depth(1));
+        assert_ne!(result.best_move.unwrap().to_string(), "d2d6");
+        // Still a queen for a knight and a pawn up
+        assert!((400..700).contains(&result.score), "{}", result.score);
+
+        // A hanging bishop, which a depth 1 search without quiescence would also find
+        let result = search_fen("4k3/8/2n5/8/8/5b2/3N4/4K3 w - - 0 1", depth(1));
+        assert_eq!(result.best_move.unwrap().to_string(), "d2f3");
+        assert!(result.score.abs() < 100, "{}", result.score);
+    }
+
+    #[test]
+    fn reuses_tt() {
+        let node = Position::parse_fen(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        )
+        .unwrap()
+        .to_node();
+        let stop = AtomicBool::new(false);
+        let tt = TranspositionTable::new(1);
+        let first = search(&node, &[node.hash], &tt, depth(4), &stop, |_| {});
+        let second = search(&node, &[node.hash], &tt, depth(4), &stop, |_| {});
+        assert!(
+            second.nodes * 4 < first.nodes,
+            "{} {}",
+            first.nodes,
+            second.nodes
+        );
+        assert_eq!(second.pv.len(), 4);
+    }
+
+    #[test]
+    fn limits() {
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let result = search_fen(
+            fen,
+            Limits {
+                nodes: Some(5000),
+                ..Limits::default()
+            },
+        );
+        assert!(result.depth >= 1);
+        assert!(result.nodes < 5000 + 1024);
+        assert!(result.best_move.is_some());
+
+        let result = search_fen(
+            fen,
+            Limits {
+                time: Some(Duration::from_millis(50)),
+                ..Limits::default()
+            },
+        );
+        assert!(result.best_move.is_some());
+
+        // Even when stopped right away, there's a move to play
+        let node = Position::parse_fen(fen).unwrap().to_node();
+        let stop = AtomicBool::new(true);
+        let mut iterations = 0;
+        let tt = TranspositionTable::new(1);
+        let result = search(&node, &[node.hash], &tt, Limits::default(), &stop, |_| {
+            iterations += 1
+        });
+        assert_eq!((result.depth, iterations), (1, 1));
+        assert!(parse_uci_move(&node, &result.best_move.unwrap().to_string()).is_some());
+    }
+}
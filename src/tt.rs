@@ -0,0 +1,286 @@
+use std::{
+    mem,
+    sync::atomic::{AtomicU8, AtomicU64, Ordering},
+};
+
+use crate::moves::Move;
+
+/// How a stored score relates to the true score of the position.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Bound {
+    Exact,
+    /// The search failed high, the true score is at least this
+    Lower,
+    /// The search failed low, the true score is at most this
+    Upper,
+}
+
+impl Bound {
+    // Never 0, so that an empty slot can't decode to a valid entry
+    const fn to_bits(self) -> u64 {
+        match self {
+            Bound::Exact => 1,
+            Bound::Lower => 2,
+            Bound::Upper => 3,
+        }
+    }
+    const fn from_bits(bits: u64) -> Option<Self> {
+        match bits {
+            1 => Some(Bound::Exact),
+            2 => Some(Bound::Lower),
+            3 => Some(Bound::Upper),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct TtEntry {
+    pub depth: u8,
+    pub bound: Bound,
+    pub score: i32,
+    /// [`Move::NULL`] if the search didn't find one
+    pub best_move: Move,
+}
+
+impl TtEntry {
+    // Layout of the data word, from the least significant bit:
+    // move (16) | score (16) | depth (8) | bound (2) | age (6)
+    const fn pack(self, age: u8) -> u64 {
+        self.best_move.to_bits() as u64
+            | (self.score as i16 as u16 as u64) << 16
+            | (self.depth as u64) << 32
+            | self.bound.to_bits() << 40
+            | ((age & TranspositionTable::AGE_MASK) as u64) << 42
+    }
+    fn unpack(data: u64) -> Option<Self> {
+        Some(TtEntry {
+            best_move: Move::from_bits(data as u16).unwrap_or(Move::NULL),
+            score: (data >> 16) as u16 as i16 as i32,
+            depth: (data >> 32) as u8,
+            bound: Bound::from_bits((data >> 40) & 0b11)?,
+        })
+    }
+    const fn age(data: u64) -> u8 {
+        (data >> 42) as u8
+    }
+}
+
+// The key is stored xor'ed with the data, so that a slot torn by concurrent writes just looks
+// like a miss. Also used by the perft cache.
+#[derive(Default)]
+pub(crate) struct Slot {
+    key: AtomicU64,
+    data: AtomicU64,
+}
+
+impl Slot {
+    /// The key and data last stored.
+    pub(crate) fn load(&self) -> (u64, u64) {
+        let data = self.data.load(Ordering::Relaxed);
+        (self.key.load(Ordering::Relaxed) ^ data, data)
+    }
+    pub(crate) fn store(&self, hash: u64, data: u64) {
+        self.key.store(hash ^ data, Ordering::Relaxed);
+        self.data.store(data, Ordering::Relaxed);
+    }
+}
+
+/// Maps `hash` onto `len` entries, without needing a power of two of them.
+pub(crate) const fn hash_index(hash: u64, len: usize) -> usize {
+    ((hash as u128 * len as u128) >> 64) as usize
+}
+
+/// A bucket fills exactly one cache line.
+#[derive(Default)]
+#[repr(align(64))]
+struct Bucket {
+    slots: [Slot; TranspositionTable::BUCKET_SIZE],
+}
+
+/// Fixed-size hash table of search results, indexed by [`Node::hash`](crate::perft::node::Node).
+/// All methods take `&self`, so one table can be shared between the searcher and the thread that
+/// clears it.
+pub struct TranspositionTable {
+    buckets: Box<[Bucket]>,
+    age: AtomicU8,
+}
+
+impl TranspositionTable {
+    pub const DEFAULT_SIZE_MB: usize = 16;
+    pub const MAX_SIZE_MB: usize = 65536;
+    const BUCKET_SIZE: usize = 4;
+    const AGE_MASK: u8 = 0b11_1111;
+
+    pub fn new(size_mb: usize) -> Self {
+        let num_buckets = (size_mb.max(1) << 20) / mem::size_of::<Bucket>();
+        TranspositionTable {
+            buckets: (0..num_buckets).map(|_| Bucket::default()).collect(),
+            age: AtomicU8::new(0),
+        }
+    }
+
+    pub fn clear(&self) {
+        for slot in self.buckets.iter().flat_map(|bucket| &bucket.slots) {
+            slot.store(0, 0);
+        }
+        self.age.store(0, Ordering::Relaxed);
+    }
+
+    /// Marks the entries of previous searches as stale, so they get replaced first.
+    pub fn new_search(&self) {
+        let age = self.age.load(Ordering::Relaxed);
+        self.age
+            .store(age.wrapping_add(1) & Self::AGE_MASK, Ordering::Relaxed);
+    }
+
+    fn bucket(&self, hash: u64) -> &Bucket {
+        &self.buckets[hash_index(hash, self.buckets.len())]
+    }
+
+    pub fn probe(&self, hash: u64) -> Option<TtEntry> {
+        self.bucket(hash)
+            .slots
+            .iter()
+            .map(Slot::load)
+            .find_map(|(key, data)| {
+                if key == hash {
+                    TtEntry::unpack(data)
+                } else {
+                    None
+                }
+            })
+    }
+
+    /// Stores an entry for `hash`, replacing either an older entry for the same position or the
+    /// least valuable entry in its bucket: the shallowest one, preferring those from earlier
+    /// searches.
+    pub fn store(&self, hash: u64, mut entry: TtEntry) {
+        let age = self.age.load(Ordering::Relaxed);
+        let slots = &self.bucket(hash).slots;
+        let loaded = slots.each_ref().map(Slot::load);
+
+        let index = match loaded.iter().position(|&(key, _)| key == hash) {
+            Some(index) => {
+                // Keep the old move rather than forgetting it
+                if entry.best_move.is_null()
+                    && let Some(old) = TtEntry::unpack(loaded[index].1)
+                {
+                    entry.best_move = old.best_move;
+                }
+                index
+            }
+            None => (0..Self::BUCKET_SIZE)
+                .min_by_key(|&index| {
+                    let data = loaded[index].1;
+                    let staleness = age.wrapping_sub(TtEntry::age(data)) & Self::AGE_MASK;
+                    (data >> 32) as u8 as i32 - 8 * staleness as i32
+                })
+                .unwrap(),
+        };
+        slots[index].store(hash, entry.pack(age));
+    }
+
+    /// Permille of the table used by the current search, estimated from the first thousand slots.
+    pub fn hashfull(&self) -> u32 {
+        let age = self.age.load(Ordering::Relaxed);
+        let (used, total) = self
+            .buckets
+            .iter()
+            .flat_map(|bucket| &bucket.slots)
+            .take(1000)
+            .fold((0, 0), |(used, total), slot| {
+                let (_, data) = slot.load();
+                let current = TtEntry::unpack(data).is_some() && TtEntry::age(data) == age;
+                (used + current as u32, total + 1)
+            });
+        used * 1000 / total
+    }
+}
+
+impl Default for TranspositionTable {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_SIZE_MB)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        coord::Square,
+        moves::{Move, MoveType},
+        tt::{Bound, TranspositionTable, TtEntry},
+    };
+
+    fn entry(depth: u8, score: i32) -> TtEntry {
+        TtEntry {
+            depth,
+            bound: Bound::Lower,
+            score,
+            best_move: Move::new(Square::E2, Square::E4, MoveType::DoublePush),
+        }
+    }
+
+    #[test]
+    fn store_probe() {
+        let tt = TranspositionTable::new(1);
+        assert_eq!(tt.probe(1234), None);
+        // A zeroed slot must not match hash 0
+        assert_eq!(tt.probe(0), None);
+
+        for score in [0, 1, -1, 31_000, -31_000, 32_000, -32_000] {
+            tt.store(1234, entry(7, score));
+            assert_eq!(tt.probe(1234), Some(entry(7, score)));
+        }
+        assert_eq!(tt.probe(1235), None);
+
+        // Storing without a move keeps the old one
+        let no_move = TtEntry {
+            best_move: Move::NULL,
+            ..entry(8, 5)
+        };
+        tt.store(1234, no_move);
+        assert_eq!(tt.probe(1234), Some(entry(8, 5)));
+
+        tt.clear();
+        assert_eq!(tt.probe(1234), None);
+    }
+
+    #[test]
+    fn replacement() {
+        let tt = TranspositionTable::new(1);
+        // Small hashes all land in the first bucket
+        for hash in 1..=4 {
+            tt.store(hash, entry(hash as u8, 0));
+        }
+        tt.store(5, entry(10, 0));
+        assert_eq!(tt.probe(1), None, "the shallowest entry is replaced");
+        assert!((2..=5).all(|hash| tt.probe(hash).is_some()));
+
+        // Deep entries from old searches lose to shallow new ones
+        tt.new_search();
+        tt.new_search();
+        tt.store(6, entry(1, 0));
+        tt.store(7, entry(1, 0));
+        assert_eq!(tt.probe(5), Some(entry(10, 0)));
+        assert_eq!(tt.probe(2), None);
+        assert_eq!(tt.probe(3), None);
+    }
+
+    #[test]
+    fn hashfull() {
+        let tt = TranspositionTable::new(1);
+        assert_eq!(tt.hashfull(), 0);
+        let hashes: Vec<u64> = (0..100_000u64)
+            .map(|i| i.wrapping_mul(0x9E37_79B9_7F4A_7C15))
+            .collect();
+        for &hash in &hashes {
+            tt.store(hash, entry(1, 0));
+        }
+        assert!(tt.hashfull() > 900, "{}", tt.hashfull());
+        tt.new_search();
+        assert_eq!(tt.hashfull(), 0);
+        tt.clear();
+        assert_eq!(tt.hashfull(), 0);
+    }
+}
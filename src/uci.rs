@@ -0,0 +1,514 @@
+use std::{fmt, time::Duration};
+
+use crate::{
+    moves::Move,
+    perft::node::Node,
+    piece::Side,
+    position::{FenError, Position},
+    search,
+};
+
+/// Commands a GUI can send to the engine.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum UciCommand {
+    Uci,
+    Debug(bool),
+    IsReady,
+    SetOption {
+        name: String,
+        value: Option<String>,
+    },
+    UciNewGame,
+    Position {
+        start: Position,
+        moves: Vec<String>,
+    },
+    Go(GoParams),
+    Stop,
+    PonderHit,
+    Quit,
+    /// Not part of UCI: prints the evaluation breakdown of the current position
+    Eval,
+}
+
+/// Parameters of the `go` command. Times are in milliseconds.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct GoParams {
+    pub wtime: Option<u64>,
+    pub btime: Option<u64>,
+    pub winc: Option<u64>,
+    pub binc: Option<u64>,
+    pub movestogo: Option<u32>,
+    pub depth: Option<u8>,
+    pub nodes: Option<u64>,
+    pub movetime: Option<u64>,
+    pub infinite: bool,
+    pub ponder: bool,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum UciError {
+    Empty,
+    UnknownCommand(String),
+    /// `position` without `startpos` or `fen`
+    MissingPosition,
+    BadFen(FenError),
+    BadValue {
+        name: String,
+        value: String,
+    },
+    /// A move in `position ... moves` that is not legal in its position
+    IllegalMove(String),
+}
+
+impl fmt::Display for UciError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UciError::Empty => write!(f, "empty command"),
+            UciError::UnknownCommand(command) => write!(f, "unknown command {:?}", command),
+            UciError::MissingPosition => write!(f, "expected 'startpos' or 'fen'"),
+            UciError::BadFen(err) => write!(f, "invalid FEN: {}", err),
+            UciError::BadValue { name, value } => {
+                write!(f, "invalid value {:?} for {}", value, name)
+            }
+            UciError::IllegalMove(mv) => write!(f, "illegal move {:?}", mv),
+        }
+    }
+}
+
+impl std::error::Error for UciError {}
+
+impl UciCommand {
+    pub fn parse(line: &str) -> Result<UciCommand, UciError> {
+        let mut tokens = line.split_whitespace();
+        let command = tokens.next().ok_or(UciError::Empty)?;
+        match command {
+            "uci" => Ok(UciCommand::Uci),
+            "debug" => Ok(UciCommand::Debug(tokens.next() == Some("on"))),
+            "isready" => Ok(UciCommand::IsReady),
+            "setoption" => Self::parse_setoption(tokens),
+            "ucinewgame" => Ok(UciCommand::UciNewGame),
+            "position" => Self::parse_position(tokens),
+            "go" => Self::parse_go(tokens).map(UciCommand::Go),
+            "stop" => Ok(UciCommand::Stop),
+            "ponderhit" => Ok(UciCommand::PonderHit),
+            "quit" => Ok(UciCommand::Quit),
+            "eval" => Ok(UciCommand::Eval),
+            _ => Err(UciError::UnknownCommand(command.to_string())),
+        }
+    }
+
+    // setoption name <id> [value <x>], where both the name and value can contain spaces
+    fn parse_setoption<'a>(tokens: impl Iterator<Item = &'a str>) -> Result<UciCommand, UciError> {
+        let mut name = Vec::new();
+        let mut value: Option<Vec<&str>> = None;
+        for token in tokens {
+            match (token, &mut value) {
+                ("name", None) if name.is_empty() => {}
+                ("value", None) => value = Some(Vec::new()),
+                (token, None) => name.push(token),
+                (token, Some(value)) => value.push(token),
+            }
+        }
+        Ok(UciCommand::SetOption {
+            name: name.join(" "),
+            value: value.map(|value| value.join(" ")),
+        })
+    }
+
+    fn parse_position<'a>(
+        mut tokens: impl Iterator<Item = &'a str>,
+    ) -> Result<UciCommand, UciError> {
+        let start = match tokens.next() {
+            Some("startpos") => {
+                if let Some(token) = tokens.next()
+                    && token != "moves"
+                {
+                    return Err(UciError::BadValue {
+                        name: "position".to_string(),
+                        value: token.to_string(),
+                    });
+                }
+                Position::START_POS
+            }
+            Some("fen") => {
+                let fen: Vec<&str> = tokens.by_ref().take_while(|&t| t != "moves").collect();
+                Position::parse_fen(&fen.join(" ")).map_err(UciError::BadFen)?
+            }
+            _ => return Err(UciError::MissingPosition),
+        };
+        Ok(UciCommand::Position {
+            start,
+            moves: tokens.map(str::to_string).collect(),
+        })
+    }
+
+    fn parse_go<'a>(mut tokens: impl Iterator<Item = &'a str>) -> Result<GoParams, UciError> {
+        fn value<'a, T: std::str::FromStr>(
+            name: &str,
+            tokens: &mut impl Iterator<Item = &'a str>,
+        ) -> Result<Option<T>, UciError> {
+            let value = tokens.next().unwrap_or_default();
+            // Some GUIs send negative times when a player is running out, which we clamp
+            let clamped = if value.starts_with('-') { "0" } else { value };
+            match clamped.parse() {
+                Ok(value) => Ok(Some(value)),
+                Err(_) => Err(UciError::BadValue {
+                    name: name.to_string(),
+                    value: value.to_string(),
+                }),
+            }
+        }
+
+        let mut params = GoParams::default();
+        while let Some(token) = tokens.next() {
+            match token {
+                "wtime" => params.wtime = value(token, &mut tokens)?,
+                "btime" => params.btime = value(token, &mut tokens)?,
+                "winc" => params.winc = value(token, &mut tokens)?,
+                "binc" => params.binc = value(token, &mut tokens)?,
+                "movestogo" => params.movestogo = value(token, &mut tokens)?,
+                "depth" => params.depth = value(token, &mut tokens)?,
+                "nodes" => params.nodes = value(token, &mut tokens)?,
+                "movetime" => params.movetime = value(token, &mut tokens)?,
+                "infinite" => params.infinite = true,
+                "ponder" => params.ponder = true,
+                // searchmoves and mate are not supported, and ignored along with their arguments
+                _ => {}
+            }
+        }
+        Ok(params)
+    }
+}
+
+impl GoParams {
+    // Safety margin for communication overhead, so we don't lose on time
+    const MOVE_OVERHEAD: u64 = 30;
+    // How many moves we assume are left when the GUI doesn't tell us
+    const DEFAULT_MOVES_TO_GO: u64 = 30;
+
+    /// How long to think for, or `None` if the search should only stop on `stop` or another
+    /// limit like `depth` or `nodes`.
+    pub fn time_budget(&self, side: Side) -> Option<Duration> {
+        if self.infinite || self.ponder {
+            return None;
+        }
+        if let Some(movetime) = self.movetime {
+            return Some(Duration::from_millis(
+                movetime.saturating_sub(Self::MOVE_OVERHEAD).max(1),
+            ));
+        }
+        let (time, inc) = match side {
+            Side::White => (self.wtime?, self.winc.unwrap_or(0)),
+            Side::Black => (self.btime?, self.binc.unwrap_or(0)),
+        };
+        let moves_to_go = self
+            .movestogo
+            .map_or(Self::DEFAULT_MOVES_TO_GO, |n| n.max(1) as u64);
+        let available = time.saturating_sub(Self::MOVE_OVERHEAD);
+        let budget = (time / moves_to_go + inc * 3 / 4).min(available);
+        Some(Duration::from_millis(budget.max(1)))
+    }
+}
+
+/// Finds the legal move in `node` with the given long algebraic notation, e.g. `e7e8q`.
+pub fn parse_uci_move(node: &Node, uci: &str) -> Option<Move> {
+    node.legal_moves()
+        .iter()
+        .copied()
+        .find(|mv| mv.to_string() == uci)
+}
+
+/// The current game: the position from the last `position` command, and the moves that led to it.
+#[derive(Clone, Debug)]
+pub struct Game {
+    pub start: Node,
+    pub moves: Vec<Move>,
+    pub node: Node,
+    /// Hashes of every position in the game, including the current one
+    pub hashes: Vec<u64>,
+}
+
+impl Game {
+    pub fn new(start: &Position, moves: &[String]) -> Result<Game, UciError> {
+        let mut game = Game {
+            start: start.to_node(),
+            moves: Vec::with_capacity(moves.len()),
+            node: start.to_node(),
+            hashes: vec![start.to_node().hash],
+        };
+        for uci in moves {
+            let mv = parse_uci_move(&game.node, uci)
+                .ok_or_else(|| UciError::IllegalMove(uci.clone()))?;
+            game.play(mv);
+        }
+        Ok(game)
+    }
+
+    pub fn play(&mut self, mv: Move) {
+        self.node.make(mv);
+        self.moves.push(mv);
+        self.hashes.push(self.node.hash);
+    }
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Game::new(&Position::START_POS, &[]).unwrap()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Score {
+    /// Centipawns, from the engine's point of view
+    Cp(i32),
+    /// Mate in this many moves, negative if the engine is getting mated
+    Mate(i32),
+}
+
+impl Score {
+    /// Converts a search score, see [`search::mate_in`].
+    pub const fn from_search(score: i32) -> Score {
+        match search::mate_in(score) {
+            Some(moves) => Score::Mate(moves),
+            None => Score::Cp(score),
+        }
+    }
+}
+
+/// An `info` line. Fields that are `None` are left out.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Info {
+    pub depth: Option<u32>,
+    pub seldepth: Option<u32>,
+    pub score: Option<Score>,
+    pub nodes: Option<u64>,
+    pub time: Option<Duration>,
+    /// Permille
+    pub hashfull: Option<u32>,
+    pub pv: Vec<Move>,
+    pub string: Option<String>,
+}
+
+impl fmt::Display for Info {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "info")?;
+        if let Some(depth) = self.depth {
+            write!(f, " depth {}", depth)?;
+        }
+        if let Some(seldepth) = self.seldepth {
+            write!(f, " seldepth {}", seldepth)?;
+        }
+        match self.score {
+            Some(Score::Cp(cp)) => write!(f, " score cp {}", cp)?,
+            Some(Score::Mate(moves)) => write!(f, " score mate {}", moves)?,
+            None => {}
+        }
+        if let Some(nodes) = self.nodes {
+            write!(f, " nodes {}", nodes)?;
+        }
+        if let Some(time) = self.time {
+            write!(f, " time {}", time.as_millis())?;
+            if let Some(nodes) = self.nodes {
+                let nps = nodes as u128 * 1_000_000 / time.as_micros().max(1);
+                write!(f, " nps {}", nps)?;
+            }
+        }
+        if let Some(hashfull) = self.hashfull {
+            write!(f, " hashfull {}", hashfull)?;
+        }
+        if !self.pv.is_empty() {
+            write!(f, " pv")?;
+            for mv in &self.pv {
+                write!(f, " {}", mv)?;
+            }
+        }
+        // Has to come last, since it takes up the rest of the line
+        if let Some(string) = &self.string {
+            write!(f, " string {}", string)?;
+        }
+        Ok(())
+    }
+}
+
+/// The `bestmove` line. Without a legal move, e.g. when mated, UCI expects the null move `0000`.
+pub fn format_bestmove(best: Option<Move>, ponder: Option<Move>) -> String {
+    match (best, ponder) {
+        (Some(best), Some(ponder)) => format!("bestmove {} ponder {}", best, ponder),
+        (best, _) => format!("bestmove {}", best.unwrap_or(Move::NULL)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{
+        coord::Square,
+        moves::{Move, MoveType},
+        piece::Side,
+        position::Position,
+        uci::{Game, GoParams, Info, Score, UciCommand, UciError, format_bestmove},
+    };
+
+    #[test]
+    fn parse_commands() {
+        assert_eq!(UciCommand::parse("  uci "), Ok(UciCommand::Uci));
+        assert_eq!(UciCommand::parse("quit"), Ok(UciCommand::Quit));
+        assert_eq!(UciCommand::parse("stop"), Ok(UciCommand::Stop));
+        assert_eq!(UciCommand::parse("eval"), Ok(UciCommand::Eval));
+        assert_eq!(UciCommand::parse(""), Err(UciError::Empty));
+        assert_eq!(
+            UciCommand::parse("xyzzy 1"),
+            Err(UciError::UnknownCommand("xyzzy".to_string()))
+        );
+        assert_eq!(
+            UciCommand::parse("setoption name Book File value /tmp/my book.bin"),
+            Ok(UciCommand::SetOption {
+                name: "Book File".to_string(),
+                value: Some("/tmp/my book.bin".to_string())
+            })
+        );
+        assert_eq!(
+            UciCommand::parse("setoption name Clear Hash"),
+            Ok(UciCommand::SetOption {
+                name: "Clear Hash".to_string(),
+                value: None
+            })
+        );
+    }
+
+    #[test]
+    fn parse_position() {
+        assert_eq!(
+            UciCommand::parse("position startpos moves e2e4 e7e5"),
+            Ok(UciCommand::Position {
+                start: Position::START_POS,
+                moves: vec!["e2e4".to_string(), "e7e5".to_string()]
+            })
+        );
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        assert_eq!(
+            UciCommand::parse(&format!("position fen {}", fen)),
+            Ok(UciCommand::Position {
+                start: Position::POSITION_2,
+                moves: vec![]
+            })
+        );
+        assert_eq!(
+            UciCommand::parse("position"),
+            Err(UciError::MissingPosition)
+        );
+        assert!(matches!(
+            UciCommand::parse("position fen 8/8/8 w - - 0 1 moves e2e4"),
+            Err(UciError::BadFen(_))
+        ));
+
+        let Ok(UciCommand::Position { start, moves }) =
+            UciCommand::parse(&format!("position fen {} moves e1g1 a6e2 d5e6", fen))
+        else {
+            panic!()
+        };
+        let game = Game::new(&start, &moves).unwrap();
+        assert_eq!(game.moves.len(), 3);
+        assert_eq!(
+            game.moves[0],
+            Move::new(Square::E1, Square::G1, MoveType::CastleKingside)
+        );
+        assert_eq!(game.hashes.len(), 4);
+        assert_eq!(game.hashes[3], game.node.hash);
+        assert_eq!(
+            Game::new(&start, &["e1g1".to_string(), "e1g1".to_string()]).unwrap_err(),
+            UciError::IllegalMove("e1g1".to_string())
+        );
+    }
+
+    #[test]
+    fn parse_go() {
+        assert_eq!(
+            UciCommand::parse("go wtime 60000 btime -50 winc 1000 binc 1000 movestogo 20"),
+            Ok(UciCommand::Go(GoParams {
+                wtime: Some(60000),
+                btime: Some(0),
+                winc: Some(1000),
+                binc: Some(1000),
+                movestogo: Some(20),
+                ..GoParams::default()
+            }))
+        );
+        assert_eq!(
+            UciCommand::parse("go depth 6 nodes 10000 infinite ponder"),
+            Ok(UciCommand::Go(GoParams {
+                depth: Some(6),
+                nodes: Some(10000),
+                infinite: true,
+                ponder: true,
+                ..GoParams::default()
+            }))
+        );
+        assert_eq!(
+            UciCommand::parse("go movetime x"),
+            Err(UciError::BadValue {
+                name: "movetime".to_string(),
+                value: "x".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn time_budget() {
+        let params = |s: &str| match UciCommand::parse(s) {
+            Ok(UciCommand::Go(params)) => params,
+            _ => panic!(),
+        };
+        assert_eq!(
+            params("go movetime 1000").time_budget(Side::White),
+            Some(Duration::from_millis(970))
+        );
+        assert_eq!(params("go infinite").time_budget(Side::White), None);
+        assert_eq!(params("go depth 5").time_budget(Side::White), None);
+        assert_eq!(
+            params("go wtime 60000 btime 30000 movestogo 10").time_budget(Side::Black),
+            Some(Duration::from_millis(3000))
+        );
+        assert_eq!(
+            params("go wtime 30000 btime 60000 winc 2000").time_budget(Side::White),
+            Some(Duration::from_millis(2500))
+        );
+        // Never more than what's left on the clock
+        assert_eq!(
+            params("go wtime 100 winc 5000").time_budget(Side::White),
+            Some(Duration::from_millis(70))
+        );
+    }
+
+    #[test]
+    fn output() {
+        let e4 = Move::new(Square::E2, Square::E4, MoveType::DoublePush);
+        let e5 = Move::new(Square::E7, Square::E5, MoveType::DoublePush);
+        let info = Info {
+            depth: Some(2),
+            score: Some(Score::Cp(35)),
+            nodes: Some(1000),
+            time: Some(Duration::from_millis(500)),
+            pv: vec![e4, e5],
+            ..Info::default()
+        };
+        assert_eq!(
+            info.to_string(),
+            "info depth 2 score cp 35 nodes 1000 time 500 nps 2000 pv e2e4 e7e5"
+        );
+        let info = Info {
+            score: Some(Score::Mate(-3)),
+            string: Some("hello world".to_string()),
+            ..Info::default()
+        };
+        assert_eq!(info.to_string(), "info score mate -3 string hello world");
+        assert_eq!(
+            format_bestmove(Some(e4), Some(e5)),
+            "bestmove e2e4 ponder e7e5"
+        );
+        assert_eq!(format_bestmove(Some(e4), None), "bestmove e2e4");
+        assert_eq!(format_bestmove(None, None), "bestmove 0000");
+    }
+}
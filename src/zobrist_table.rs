@@ -41,26 +41,30 @@ impl ZobristTable {
         }
     }
 
-    pub fn hash_piece(&self, piece: Piece, square: Square) -> u64 {
+    // Raw pointer reads rather than `get_unchecked`, since the latter isn't const yet
+    pub const fn hash_piece(&self, piece: Piece, square: Square) -> u64 {
         // TODO why does the ASM here contain a 0xFF mask, on aarch64 at least?
         let index = piece.to_index() as usize * 64 + square.to_index() as usize;
-        unsafe { *self.piece_table.get_unchecked(index) }
+        debug_assert!(index < Self::PIECE_TABLE_SIZE);
+        unsafe { *self.piece_table.as_ptr().add(index) }
     }
-    pub fn hash_en_passant_square(&self, index: usize) -> u64 {
+    pub const fn hash_en_passant_square(&self, index: usize) -> u64 {
         // TODO why does the ASM here contain a 0xFF mask, on aarch64 at least?
-        unsafe { *self.en_passant_square_table.get_unchecked(index) }
+        debug_assert!(index < Self::EN_PASSANT_SQUARE_TABLE_SIZE);
+        unsafe { *self.en_passant_square_table.as_ptr().add(index) }
     }
-    pub fn hash_castling_rights(&self, castling_rights: &CastlingRights) -> u64 {
+    pub const fn hash_castling_rights(&self, castling_rights: &CastlingRights) -> u64 {
         // TODO why does the ASM here contain an add, on aarch64 at least?
         unsafe {
             *self
                 .castling_rights_table
-                .get_unchecked(castling_rights.to_index() as usize)
+                .as_ptr()
+                .add(castling_rights.to_index() as usize)
         }
     }
-    pub fn hash_side(&self, side: Side) -> u64 {
+    pub const fn hash_side(&self, side: Side) -> u64 {
         // TODO why does the ASM here contain an add, on aarch64 at least?
-        unsafe { *self.side_table.get_unchecked(side.to_index() as usize) }
+        unsafe { *self.side_table.as_ptr().add(side.to_index() as usize) }
     }
 }
 
@@ -0,0 +1,194 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::mpsc::{self, Receiver},
+    thread,
+    time::{Duration, Instant},
+};
+
+use sjaak::{position::Position, uci::parse_uci_move};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The engine binary, driven through its stdin and stdout.
+struct Engine {
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+}
+
+impl Engine {
+    fn spawn() -> Self {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_engine"))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+        let (tx, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                if tx.send(line.unwrap()).is_err() {
+                    break;
+                }
+            }
+        });
+        Engine {
+            child,
+            stdin,
+            lines,
+        }
+    }
+
+    fn send(&mut self, line: &str) {
+        writeln!(self.stdin, "{}", line).unwrap();
+        self.stdin.flush().unwrap();
+    }
+
+    /// Reads lines until one starts with `prefix`, and returns it.
+    fn expect(&mut self, prefix: &str) -> String {
+        let deadline = Instant::now() + TIMEOUT;
+        loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            match self.lines.recv_timeout(timeout) {
+                Ok(line) if line.starts_with(prefix) => return line,
+                Ok(_) => {}
+                Err(err) => panic!("no {:?} from the engine: {}", prefix, err),
+            }
+        }
+    }
+
+    /// Asserts that no line starting with `prefix` arrives within `duration`.
+    fn expect_none(&mut self, prefix: &str, duration: Duration) {
+        let deadline = Instant::now() + duration;
+        while let Ok(line) = self
+            .lines
+            .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+        {
+            assert!(!line.starts_with(prefix), "unexpected {:?}", line);
+        }
+    }
+
+    fn wait(mut self) {
+        let deadline = Instant::now() + TIMEOUT;
+        while Instant::now() < deadline {
+            if let Some(status) = self.child.try_wait().unwrap() {
+                assert!(status.success());
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        self.child.kill().unwrap();
+        panic!("engine did not exit");
+    }
+}
+
+fn bestmove(line: &str) -> &str {
+    line.split_whitespace().nth(1).unwrap()
+}
+
+#[test]
+fn handshake() {
+    let mut engine = Engine::spawn();
+    engine.send("uci");
+    engine.expect("id name");
+    engine.expect("uciok");
+    engine.send("isready");
+    engine.expect("readyok");
+    engine.send("quit");
+    engine.wait();
+}
+
+#[test]
+fn position_and_go() {
+    let mut engine = Engine::spawn();
+    engine.send("position startpos moves e2e4 e7e5 g1f3");
+    engine.send("go wtime 1000 btime 1000");
+    let line = engine.expect("bestmove");
+
+    let node =
+        Position::parse_fen("rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2")
+            .unwrap()
+            .to_node();
+    assert!(parse_uci_move(&node, bestmove(&line)).is_some(), "{}", line);
+    engine.send("quit");
+    engine.wait();
+}
+
+#[test]
+fn stop_infinite_search() {
+    let mut engine = Engine::spawn();
+    engine.send("position startpos");
+    engine.send("go infinite");
+    engine.expect_none("bestmove", Duration::from_millis(200));
+
+    // The input loop keeps responding while searching
+    engine.send("isready");
+    engine.expect("readyok");
+
+    engine.send("stop");
+    engine.expect("bestmove");
+    // Stop doesn't mean quit
+    engine.send("isready");
+    engine.expect("readyok");
+    engine.send("quit");
+    engine.wait();
+}
+
+#[test]
+fn ponderhit() {
+    let mut engine = Engine::spawn();
+    engine.send("position startpos moves e2e4");
+    engine.send("go ponder wtime 1000 btime 1000");
+    engine.expect_none("bestmove", Duration::from_millis(200));
+    engine.send("ponderhit");
+    engine.expect("bestmove");
+    engine.send("quit");
+    engine.wait();
+}
+
+#[test]
+fn quit_while_searching() {
+    let mut engine = Engine::spawn();
+    engine.send("go infinite");
+    engine.send("quit");
+    engine.wait();
+}
+
+#[test]
+fn hash_option() {
+    let mut engine = Engine::spawn();
+    engine.send("uci");
+    assert!(engine.expect("option name Hash").contains("type spin"));
+    engine.send("setoption name Hash value 1");
+    engine.send("ucinewgame");
+    engine.send("position startpos");
+    engine.send("go depth 3");
+    assert!(engine.expect("info depth 3").contains(" hashfull "));
+    engine.expect("bestmove");
+    engine.send("quit");
+    engine.wait();
+}
+
+#[test]
+fn eval_breakdown() {
+    let mut engine = Engine::spawn();
+    engine.send("position startpos");
+    engine.send("eval");
+    engine.expect("info string Material");
+    engine.expect("info string Final evaluation");
+    engine.send("quit");
+    engine.wait();
+}
+
+#[test]
+fn no_legal_moves() {
+    let mut engine = Engine::spawn();
+    engine.send("position fen 7k/5Q2/6K1/8/8/8/8/8 b - - 0 1");
+    engine.send("go depth 1");
+    assert_eq!(engine.expect("bestmove"), "bestmove 0000");
+    engine.send("quit");
+    engine.wait();
+}
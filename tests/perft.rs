@@ -0,0 +1,93 @@
+use std::process::Command;
+
+fn perft(args: &[&str]) -> (bool, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_perft"))
+        .args(args)
+        .output()
+        .unwrap();
+    (
+        output.status.success(),
+        String::from_utf8(output.stdout).unwrap(),
+    )
+}
+
+#[test]
+fn divide() {
+    let (ok, out) = perft(&["2"]);
+    assert!(ok);
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(lines.len(), 20 + 3, "{}", out);
+    assert!(lines.contains(&"e2e4: 20"));
+    assert!(lines.contains(&"Nodes searched: 400"));
+
+    // The FEN can be split over several arguments, and moves played on top of it
+    let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+    let split: Vec<&str> = fen.split(' ').collect();
+    let (ok, out) = perft(&[&["3"], &split[..]].concat());
+    assert!(ok);
+    assert!(out.contains("e1g1: 2059\n"));
+    assert!(out.contains("Nodes searched: 97862\n"));
+    for options in [&["--threads", "1"], &["--hash", "1"]] {
+        let (ok, out) = perft(&[&options[..], &["3", fen]].concat());
+        assert!(ok);
+        assert!(out.contains("e1g1: 2059\n"));
+        assert!(out.contains("Nodes searched: 97862\n"));
+    }
+    let (ok, out) = perft(&["--moves", "e1g1", "2", fen]);
+    assert!(ok);
+    assert!(out.contains("Nodes searched: 2059\n"), "{}", out);
+}
+
+#[test]
+fn bad_input() {
+    assert!(!perft(&[]).0);
+    assert!(!perft(&["x"]).0);
+    assert!(!perft(&["1", "not a fen"]).0);
+    assert!(!perft(&["-m", "e2e5", "1"]).0);
+    assert!(!perft(&["--threads", "many", "1"]).0);
+    assert!(!perft(&["--hash"]).0);
+    assert!(!perft(&["--stats"]).0);
+    assert!(!perft(&["--stats", "-t", "2", "1"]).0);
+    assert!(!perft(&["--stats", "--hash", "1", "1"]).0);
+    assert!(!perft(&["--stats", "--suite", "perftsuite.epd", "1"]).0);
+}
+
+#[test]
+fn suite() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/perftsuite.epd");
+    let (ok, out) = perft(&["--suite", path, "3"]);
+    assert!(ok, "{}", out);
+    assert!(out.contains(" 0 mismatches up to depth 3"), "{}", out);
+
+    let broken = std::env::temp_dir().join("sjaak_broken_suite.epd");
+    std::fs::write(
+        &broken,
+        "4k3/8/8/8/8/8/8/4K3 w - - 0 1 ;D1 5 ;D2 26\n4k3/8/8/8/8/8/8/4K3 b - - 0 1 ;D1 5\n",
+    )
+    .unwrap();
+    let (ok, out) = perft(&["--suite", broken.to_str().unwrap(), "3"]);
+    assert!(!ok);
+    assert!(
+        out.contains("line 1: 4k3/8/8/8/8/8/8/4K3 w - - 0 1 at depth 2: expected 26, found 25"),
+        "{}",
+        out
+    );
+    assert!(out.contains("2 positions, 1 mismatches"), "{}", out);
+    assert!(!perft(&["--suite", path, "3", "4k3/8/8/8/8/8/8/4K3 w - - 0 1"]).0);
+}
+
+#[test]
+fn stats() {
+    let (ok, out) = perft(&["--stats", "3"]);
+    assert!(ok);
+    let row = |name: &str| {
+        out.lines()
+            .find(|line| line.starts_with(name))
+            .and_then(|line| line.split_whitespace().last())
+            .unwrap()
+            .to_string()
+    };
+    assert_eq!(row("Nodes"), "8902");
+    assert_eq!(row("Captures"), "34");
+    assert_eq!(row("Checks"), "12");
+}